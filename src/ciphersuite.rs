@@ -0,0 +1,622 @@
+use blsful::inner_types::{
+    ExpandMsgXmd, G1Affine, G1Projective, G2Affine, G2Prepared, G2Projective, Group,
+    MillerLoopResult, Scalar, multi_miller_loop,
+};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+/// Longest domain separation tag `expand_message_xmd` accepts
+pub const MAX_DST_LEN: usize = 255;
+
+/// Prefix of the stored uncompressed public key encoding
+const UNCOMPRESSED_PREFIX: u8 = 0x04;
+
+/// Size of a base field element in the ZCash encodings
+const FP_LEN: usize = 48;
+
+/// Size of a base field element in the EIP-2537 encodings
+const EIP2537_FP_LEN: usize = 64;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CiphersuiteError {
+    #[error("Unknown key scheme {0}")]
+    UnknownScheme(u8),
+    #[error("Unknown ciphersuite {0}")]
+    UnknownCiphersuite(u8),
+    #[error("Domain separation tag is {0} bytes, at most {MAX_DST_LEN} are allowed")]
+    DstTooLong(usize),
+    #[error("Unknown point encoding {0}")]
+    UnknownEncoding(u8),
+    #[error("Invalid {0} encoding")]
+    InvalidPoint(&'static str),
+}
+
+/// Which groups keys and signatures live in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyScheme {
+    /// Public keys in G1 (48 bytes), signatures in G2 (96 bytes)
+    #[default]
+    MinimalPublicKeySize,
+    /// Public keys in G2 (96 bytes), signatures in G1 (48 bytes)
+    MinimalSignatureSize,
+}
+
+impl TryFrom<u8> for KeyScheme {
+    type Error = CiphersuiteError;
+
+    fn try_from(id: u8) -> Result<Self, Self::Error> {
+        match id {
+            0 => Ok(KeyScheme::MinimalPublicKeySize),
+            1 => Ok(KeyScheme::MinimalSignatureSize),
+            _ => Err(CiphersuiteError::UnknownScheme(id)),
+        }
+    }
+}
+
+impl From<KeyScheme> for u8 {
+    fn from(scheme: KeyScheme) -> Self {
+        match scheme {
+            KeyScheme::MinimalPublicKeySize => 0,
+            KeyScheme::MinimalSignatureSize => 1,
+        }
+    }
+}
+
+impl KeyScheme {
+    /// Re-encodes a public key from its stored `0x04`-prefixed uncompressed
+    /// form.
+    pub fn encode_public_key(
+        self,
+        uncompressed_pk: &[u8],
+        encoding: PointEncoding,
+    ) -> Result<Vec<u8>, CiphersuiteError> {
+        match self {
+            KeyScheme::MinimalPublicKeySize => {
+                reencode_public_key::<G1Projective>(uncompressed_pk, encoding)
+            }
+            KeyScheme::MinimalSignatureSize => {
+                reencode_public_key::<G2Projective>(uncompressed_pk, encoding)
+            }
+        }
+    }
+
+    /// Re-encodes a signature from its compressed form.
+    pub fn encode_signature(
+        self,
+        signature: &[u8],
+        encoding: PointEncoding,
+    ) -> Result<Vec<u8>, CiphersuiteError> {
+        match self {
+            KeyScheme::MinimalPublicKeySize => {
+                reencode_signature::<G2Projective>(signature, encoding)
+            }
+            KeyScheme::MinimalSignatureSize => {
+                reencode_signature::<G1Projective>(signature, encoding)
+            }
+        }
+    }
+}
+
+fn reencode_public_key<G: KeyGroup>(
+    uncompressed_pk: &[u8],
+    encoding: PointEncoding,
+) -> Result<Vec<u8>, CiphersuiteError> {
+    G::from_uncompressed(uncompressed_pk)
+        .map(|point| point.encode(encoding))
+        .ok_or(CiphersuiteError::InvalidPoint("public key"))
+}
+
+fn reencode_signature<G: KeyGroup>(
+    signature: &[u8],
+    encoding: PointEncoding,
+) -> Result<Vec<u8>, CiphersuiteError> {
+    G::from_compressed(signature)
+        .map(|point| point.encode(encoding))
+        .ok_or(CiphersuiteError::InvalidPoint("signature"))
+}
+
+/// How public keys and signatures are encoded in job results.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PointEncoding {
+    /// ZCash compressed: 48 bytes in G1, 96 bytes in G2
+    #[default]
+    Compressed,
+    /// ZCash uncompressed: 96 bytes in G1, 192 bytes in G2
+    Uncompressed,
+    /// EIP-2537 precompile input: every base field element padded to 64
+    /// bytes, 128 bytes in G1 and 256 bytes in G2
+    Eip2537,
+}
+
+impl TryFrom<u8> for PointEncoding {
+    type Error = CiphersuiteError;
+
+    fn try_from(id: u8) -> Result<Self, Self::Error> {
+        match id {
+            0 => Ok(PointEncoding::Compressed),
+            1 => Ok(PointEncoding::Uncompressed),
+            2 => Ok(PointEncoding::Eip2537),
+            _ => Err(CiphersuiteError::UnknownEncoding(id)),
+        }
+    }
+}
+
+/// The BLS signature ciphersuites of draft-irtf-cfrg-bls-signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ciphersuite {
+    /// `BLS_SIG_BLS12381G{1,2}_XMD:SHA-256_SSWU_RO_NUL_`
+    Basic,
+    /// `BLS_SIG_BLS12381G{1,2}_XMD:SHA-256_SSWU_RO_AUG_`: the public key is
+    /// prepended to every message
+    MessageAugmentation,
+    /// `BLS_SIG_BLS12381G{1,2}_XMD:SHA-256_SSWU_RO_POP_`, as used by the
+    /// Ethereum consensus layer
+    ProofOfPossession,
+}
+
+impl Ciphersuite {
+    /// The ciphersuite's standard domain separation tag for signatures
+    /// under `scheme`.
+    pub const fn dst(self, scheme: KeyScheme) -> &'static [u8] {
+        match (scheme, self) {
+            (KeyScheme::MinimalPublicKeySize, Ciphersuite::Basic) => {
+                b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_"
+            }
+            (KeyScheme::MinimalPublicKeySize, Ciphersuite::MessageAugmentation) => {
+                b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_AUG_"
+            }
+            (KeyScheme::MinimalPublicKeySize, Ciphersuite::ProofOfPossession) => {
+                b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_"
+            }
+            (KeyScheme::MinimalSignatureSize, Ciphersuite::Basic) => {
+                b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_NUL_"
+            }
+            (KeyScheme::MinimalSignatureSize, Ciphersuite::MessageAugmentation) => {
+                b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_AUG_"
+            }
+            (KeyScheme::MinimalSignatureSize, Ciphersuite::ProofOfPossession) => {
+                b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_POP_"
+            }
+        }
+    }
+}
+
+/// The POP ciphersuite's domain separation tag for proofs of possession
+/// under `scheme`.
+pub const fn pop_dst(scheme: KeyScheme) -> &'static [u8] {
+    match scheme {
+        KeyScheme::MinimalPublicKeySize => b"BLS_POP_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_",
+        KeyScheme::MinimalSignatureSize => b"BLS_POP_BLS12381G1_XMD:SHA-256_SSWU_RO_POP_",
+    }
+}
+
+impl TryFrom<u8> for Ciphersuite {
+    type Error = CiphersuiteError;
+
+    fn try_from(id: u8) -> Result<Self, Self::Error> {
+        match id {
+            0 => Ok(Ciphersuite::Basic),
+            1 => Ok(Ciphersuite::MessageAugmentation),
+            2 => Ok(Ciphersuite::ProofOfPossession),
+            _ => Err(CiphersuiteError::UnknownCiphersuite(id)),
+        }
+    }
+}
+
+/// A ciphersuite together with the domain separation tag messages are
+/// hashed under.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SigningDomain {
+    pub ciphersuite: Ciphersuite,
+    pub dst: Vec<u8>,
+}
+
+impl SigningDomain {
+    /// Uses `dst` as the domain separation tag, or the ciphersuite's standard
+    /// one for `scheme` when it is empty.
+    pub fn new(
+        scheme: KeyScheme,
+        ciphersuite: Ciphersuite,
+        dst: &[u8],
+    ) -> Result<Self, CiphersuiteError> {
+        if dst.len() > MAX_DST_LEN {
+            return Err(CiphersuiteError::DstTooLong(dst.len()));
+        }
+        let dst = if dst.is_empty() {
+            ciphersuite.dst(scheme).to_vec()
+        } else {
+            dst.to_vec()
+        };
+        Ok(Self { ciphersuite, dst })
+    }
+
+    /// The domain of a proof of possession, which signs the compressed
+    /// public key.
+    pub fn proof_of_possession(scheme: KeyScheme) -> Self {
+        Self {
+            ciphersuite: Ciphersuite::ProofOfPossession,
+            dst: pop_dst(scheme).to_vec(),
+        }
+    }
+
+    /// Hashes `message` to the signature group for a signature under
+    /// `public_key`.
+    pub fn hash_to_point<G: KeyGroup>(&self, public_key: G, message: &[u8]) -> G::Signature {
+        match self.ciphersuite {
+            Ciphersuite::MessageAugmentation => {
+                let mut augmented = public_key.to_compressed();
+                augmented.extend_from_slice(message);
+                G::Signature::hash_to_curve(&augmented, &self.dst)
+            }
+            Ciphersuite::Basic | Ciphersuite::ProofOfPossession => {
+                G::Signature::hash_to_curve(message, &self.dst)
+            }
+        }
+    }
+}
+
+/// A BLS12-381 group keys are generated in, paired with the group their
+/// signatures live in.
+pub trait KeyGroup: Group<Scalar = Scalar> + Send + Sync + 'static {
+    /// The group of signatures under keys in this group
+    type Signature: KeyGroup<Signature = Self>;
+
+    const SCHEME: KeyScheme;
+
+    fn to_compressed(&self) -> Vec<u8>;
+
+    fn from_compressed(bytes: &[u8]) -> Option<Self>;
+
+    /// The uncompressed encoding, prefixed with `0x04`.
+    fn to_uncompressed(&self) -> Vec<u8>;
+
+    fn from_uncompressed(bytes: &[u8]) -> Option<Self>;
+
+    /// The point in `encoding`, as returned in job results.
+    fn encode(&self, encoding: PointEncoding) -> Vec<u8>;
+
+    /// Hashes to the group with `expand_message_xmd` over SHA-256.
+    fn hash_to_curve(message: &[u8], dst: &[u8]) -> Self;
+
+    /// CoreVerify of `signature` on `hashed_message` under this public key.
+    fn core_verify(self, hashed_message: Self::Signature, signature: Self::Signature) -> bool;
+}
+
+impl KeyGroup for G1Projective {
+    type Signature = G2Projective;
+
+    const SCHEME: KeyScheme = KeyScheme::MinimalPublicKeySize;
+
+    fn to_compressed(&self) -> Vec<u8> {
+        G1Affine::from(self).to_compressed().to_vec()
+    }
+
+    fn from_compressed(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.try_into().ok()?;
+        Option::<G1Affine>::from(G1Affine::from_compressed(bytes)).map(Self::from)
+    }
+
+    fn to_uncompressed(&self) -> Vec<u8> {
+        let mut bytes = vec![UNCOMPRESSED_PREFIX];
+        bytes.extend_from_slice(&G1Affine::from(self).to_uncompressed());
+        bytes
+    }
+
+    fn encode(&self, encoding: PointEncoding) -> Vec<u8> {
+        let uncompressed = G1Affine::from(self).to_uncompressed();
+        match encoding {
+            PointEncoding::Compressed => KeyGroup::to_compressed(self),
+            PointEncoding::Uncompressed => uncompressed.to_vec(),
+            // x || y
+            PointEncoding::Eip2537 => eip2537(self, &uncompressed, &[0, 1]),
+        }
+    }
+
+    fn from_uncompressed(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes
+            .strip_prefix(&[UNCOMPRESSED_PREFIX])?
+            .try_into()
+            .ok()?;
+        Option::<G1Affine>::from(G1Affine::from_uncompressed(bytes)).map(Self::from)
+    }
+
+    fn hash_to_curve(message: &[u8], dst: &[u8]) -> Self {
+        Self::hash::<ExpandMsgXmd<Sha256>>(message, dst)
+    }
+
+    /// Checks `e(g1, signature) == e(public_key, hashed_message)`.
+    fn core_verify(self, hashed_message: G2Projective, signature: G2Projective) -> bool {
+        !bool::from(self.is_identity())
+            && !bool::from(signature.is_identity())
+            && pairing_product_is_one(
+                [G1Projective::generator(), -self],
+                [signature, hashed_message],
+            )
+    }
+}
+
+impl KeyGroup for G2Projective {
+    type Signature = G1Projective;
+
+    const SCHEME: KeyScheme = KeyScheme::MinimalSignatureSize;
+
+    fn to_compressed(&self) -> Vec<u8> {
+        G2Affine::from(self).to_compressed().to_vec()
+    }
+
+    fn from_compressed(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.try_into().ok()?;
+        Option::<G2Affine>::from(G2Affine::from_compressed(bytes)).map(Self::from)
+    }
+
+    fn to_uncompressed(&self) -> Vec<u8> {
+        let mut bytes = vec![UNCOMPRESSED_PREFIX];
+        bytes.extend_from_slice(&G2Affine::from(self).to_uncompressed());
+        bytes
+    }
+
+    fn encode(&self, encoding: PointEncoding) -> Vec<u8> {
+        let uncompressed = G2Affine::from(self).to_uncompressed();
+        match encoding {
+            PointEncoding::Compressed => KeyGroup::to_compressed(self),
+            PointEncoding::Uncompressed => uncompressed.to_vec(),
+            // ZCash puts c1 before c0, EIP-2537 wants x.c0 || x.c1 || y.c0 || y.c1
+            PointEncoding::Eip2537 => eip2537(self, &uncompressed, &[1, 0, 3, 2]),
+        }
+    }
+
+    fn from_uncompressed(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes
+            .strip_prefix(&[UNCOMPRESSED_PREFIX])?
+            .try_into()
+            .ok()?;
+        Option::<G2Affine>::from(G2Affine::from_uncompressed(bytes)).map(Self::from)
+    }
+
+    fn hash_to_curve(message: &[u8], dst: &[u8]) -> Self {
+        Self::hash::<ExpandMsgXmd<Sha256>>(message, dst)
+    }
+
+    /// Checks `e(signature, g2) == e(hashed_message, public_key)`.
+    fn core_verify(self, hashed_message: G1Projective, signature: G1Projective) -> bool {
+        !bool::from(self.is_identity())
+            && !bool::from(signature.is_identity())
+            && pairing_product_is_one(
+                [signature, -hashed_message],
+                [G2Projective::generator(), self],
+            )
+    }
+}
+
+/// Pads the base field elements of a ZCash uncompressed point, taken in
+/// `order`, to the EIP-2537 encoding. The identity is all zeroes there.
+fn eip2537<G: KeyGroup>(point: &G, uncompressed: &[u8], order: &[usize]) -> Vec<u8> {
+    let mut bytes = vec![0u8; order.len() * EIP2537_FP_LEN];
+    if bool::from(point.is_identity()) {
+        return bytes;
+    }
+    for (padded, index) in bytes.chunks_exact_mut(EIP2537_FP_LEN).zip(order) {
+        padded[EIP2537_FP_LEN - FP_LEN..]
+            .copy_from_slice(&uncompressed[index * FP_LEN..(index + 1) * FP_LEN]);
+    }
+    bytes
+}
+
+/// Checks `e(a[0], b[0]) * e(a[1], b[1]) == 1`.
+fn pairing_product_is_one(a: [G1Projective; 2], b: [G2Projective; 2]) -> bool {
+    let a = a.map(G1Affine::from);
+    let b = b.map(|point| G2Prepared::from(G2Affine::from(point)));
+    let result = multi_miller_loop(&[(&a[0], &b[0]), (&a[1], &b[1])]).final_exponentiation();
+    bool::from(result.is_identity())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Secret key of the Ethereum consensus `sign` test vectors
+    const SECRET_KEY: &str = "263dbd792f5b1be47ed85f8938c0f29586af0d3ac7b977f21c278fe1462040e3";
+
+    const MIN_PK_PUBLIC_KEY: &str = "a491d1b0ecd9bb917989f0e74f0dea0422eac4a873e5e2644f368dffb9a6e20fd6e10c1b77654d067c0618f6e5a7f79a";
+    const MIN_SIG_PUBLIC_KEY: &str = "ac400b70f6f8cd35648f5c126cce5417f3be4d8eefbd42ceb4286a14df7e03135313fe5845e3a575faab3e8b949d248814856c22d8cdb2967c720e963eedc999e738373b14172f06fc915769d3cc5ab7ae0a1b9c38f48b5585fb09d4bd2733bb";
+
+    /// Signatures under the draft-irtf-cfrg-bls-signature ciphersuites with
+    /// their standard DSTs, as produced by blst.
+    const VECTORS: &[(KeyScheme, Ciphersuite, &[u8], &str)] = &[
+        (
+            KeyScheme::MinimalPublicKeySize,
+            Ciphersuite::Basic,
+            b"",
+            "b02c82008ed0b01c4a1d7b2f32d4a3f5ccf91b330a68ca2da591357c97001d636b6ed18383bf4d83ac58222f2d4ad72c0119274de098126ff3b18a4590c5540e350ce2714ec50ce1074220fd9c1048ec7a00499736c28c8a9faa32fb3476eccc",
+        ),
+        (
+            KeyScheme::MinimalPublicKeySize,
+            Ciphersuite::Basic,
+            b"abc",
+            "ac9a8f6a3980f799f9b428f41e1864ca36bf424dec971842e3aafdbf0949b6fe73f49b2ca4b8f1e632d3007b0ed3fbbb06e0287656a3e57130865df5409ece5c251f92a3ca801a096c719b2fc9c9ccd2ab6c6292fee166e424ff5498d90126c9",
+        ),
+        (
+            KeyScheme::MinimalPublicKeySize,
+            Ciphersuite::MessageAugmentation,
+            b"",
+            "94380b81f52c676fa55a93d076b5dfc71efff337aec7247f3cd1aab254d5b2340aaf4819cab53e7f3f536734cecaa1ee18849940966f6162e4218f3c5d11ea7b75a3ff6c4972c1be67855ce7d853d7409a9cb4909b68d15efcebb0372e39c41e",
+        ),
+        (
+            KeyScheme::MinimalPublicKeySize,
+            Ciphersuite::MessageAugmentation,
+            b"abc",
+            "97b4ace28d9560e9366700621f49a740410bc9039c42852f136e33d7c057a9766d1da6442ce9783750fd1b788b08ad3b13a489088b5eaefa723a8fc7b5d50a32bde5332ed53eccbad898570d41bd42bfd6c5c6fe2fe0d15bb771636857ce4144",
+        ),
+        (
+            KeyScheme::MinimalPublicKeySize,
+            Ciphersuite::ProofOfPossession,
+            b"",
+            "b6b4caa2a4bfa3612b79437d0e549aba52551d434315717635f823337431c0e068d47cf616a40a47b81b489e9c73381706355724af3542ae49b16c6341b120b7d664369f9816b3cedce7cc9c4707f514e2865ba2131211de29e09a6e42f686da",
+        ),
+        (
+            KeyScheme::MinimalPublicKeySize,
+            Ciphersuite::ProofOfPossession,
+            b"abc",
+            "a31751779876b59bddbd8896f966ab41b07556c0f020fbac55e862e027d48e79e57caba6153d7ec47db1219dca1b070d13a6469139855bd90ed9bb08b6686ee07836703f90547be20e7715a76de94115280b07b9238da2ea23704a1e1a71c2fe",
+        ),
+        // Ethereum consensus `sign` vector
+        (
+            KeyScheme::MinimalPublicKeySize,
+            Ciphersuite::ProofOfPossession,
+            &[0; 32],
+            "b6ed936746e01f8ecf281f020953fbf1f01debd5657c4a383940b020b26507f6076334f91e2366c96e9ab279fb5158090352ea1c5b0c9274504f4f0e7053af24802e51e4568d164fe986834f41e55c8e850ce1f98458c0cfc9ab380b55285a55",
+        ),
+        (
+            KeyScheme::MinimalSignatureSize,
+            Ciphersuite::Basic,
+            b"",
+            "a822086b25eddc01d21b0f29c84779afdd736e29bac81970035edb1a07a13aa53b4704ab7abc0d9f90e8aee19120affb",
+        ),
+        (
+            KeyScheme::MinimalSignatureSize,
+            Ciphersuite::Basic,
+            b"abc",
+            "894868b11153b0352e9d3cea96a5b035a8780e4044d5538941ad27e40eb731b8a4a8fc8c4b36d67cd26f4e679ca914d6",
+        ),
+        (
+            KeyScheme::MinimalSignatureSize,
+            Ciphersuite::MessageAugmentation,
+            b"",
+            "b318e46aa9a6e65dc5975fef9de790cb0a15c0693f978a7d16b6784a30550dea5b02b79ff7a9a512a0d3e6ebdfd3c25a",
+        ),
+        (
+            KeyScheme::MinimalSignatureSize,
+            Ciphersuite::MessageAugmentation,
+            b"abc",
+            "891e5b421e8ddfc64f34b97ec25abfcf63785e29796d4a16f37a3dd0de28cd371695ed245a5e2f2dfcb7331152c77cee",
+        ),
+        (
+            KeyScheme::MinimalSignatureSize,
+            Ciphersuite::ProofOfPossession,
+            b"",
+            "ac4a2207d5fe1bfe8118ff1c576a32c48471762c5523eb955ea1ebe90b40ba368656e41a973d06d73b7dd3ada7735996",
+        ),
+        (
+            KeyScheme::MinimalSignatureSize,
+            Ciphersuite::ProofOfPossession,
+            b"abc",
+            "8fb10052b82bb7a49df8997cc8737faeaf75eef17766f6603709bf778571404cf2aa56f927d572843e7b7c32a13ec31e",
+        ),
+    ];
+
+    fn secret_key() -> Scalar {
+        let bytes = hex::decode(SECRET_KEY).unwrap();
+        Option::from(Scalar::from_be_bytes(&bytes.try_into().unwrap())).unwrap()
+    }
+
+    /// Signs `message` in `domain`, checks the signature verifies and
+    /// returns it compressed.
+    fn sign<G: KeyGroup>(domain: &SigningDomain, message: &[u8]) -> Vec<u8> {
+        let public_key = G::generator() * secret_key();
+        let hashed_message = domain.hash_to_point(public_key, message);
+        let signature = hashed_message * secret_key();
+        assert!(public_key.core_verify(hashed_message, signature));
+        assert!(!public_key.core_verify(domain.hash_to_point(public_key, b"other"), signature));
+        signature.to_compressed()
+    }
+
+    #[test]
+    fn public_keys_match_vectors() {
+        let public_key = G1Projective::generator() * secret_key();
+        assert_eq!(hex::encode(public_key.to_compressed()), MIN_PK_PUBLIC_KEY);
+        let public_key = G2Projective::generator() * secret_key();
+        assert_eq!(hex::encode(public_key.to_compressed()), MIN_SIG_PUBLIC_KEY);
+    }
+
+    #[test]
+    fn signatures_match_vectors() {
+        for (scheme, ciphersuite, message, expected) in VECTORS {
+            let domain = SigningDomain::new(*scheme, *ciphersuite, &[]).unwrap();
+            let signature = match scheme {
+                KeyScheme::MinimalPublicKeySize => sign::<G1Projective>(&domain, message),
+                KeyScheme::MinimalSignatureSize => sign::<G2Projective>(&domain, message),
+            };
+            assert_eq!(
+                hex::encode(signature),
+                *expected,
+                "{scheme:?} {ciphersuite:?} {message:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn proofs_of_possession_match_vectors() {
+        let public_key = G1Projective::generator() * secret_key();
+        let domain = SigningDomain::proof_of_possession(KeyScheme::MinimalPublicKeySize);
+        assert_eq!(
+            hex::encode(sign::<G1Projective>(&domain, &public_key.to_compressed())),
+            "b803eb0ed93ea10224a73b6b9c725796be9f5fefd215ef7a5b97234cc956cf6870db6127b7e4d824ec62276078e787db05584ce1adbf076bc0808ca0f15b73d59060254b25393d95dfc7abe3cda566842aaedf50bbb062aae1bbb6ef3b1f77e1"
+        );
+
+        let public_key = G2Projective::generator() * secret_key();
+        let domain = SigningDomain::proof_of_possession(KeyScheme::MinimalSignatureSize);
+        assert_eq!(
+            hex::encode(sign::<G2Projective>(&domain, &public_key.to_compressed())),
+            "85cd8b8b8e2677c1e6e861e6c720d08ff986bc39862de8f975fbb287f34a550402277ab6fd5fad7ae0d4f57a6ba80e19"
+        );
+    }
+
+    /// Pads a hex-encoded base field element to its 64-byte EIP-2537 form.
+    fn fp(element: &str) -> String {
+        format!("{}{element}", "00".repeat(EIP2537_FP_LEN - FP_LEN))
+    }
+
+    #[test]
+    fn eip2537_encodes_generators_in_precompile_order() {
+        let g1_x = "17f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb";
+        let g1_y = "08b3f481e3aaa0f1a09e30ed741d8ae4fcf5e095d5d00af600db18cb2c04b3edd03cc744a2888ae40caa232946c5e7e1";
+        assert_eq!(
+            hex::encode(G1Projective::generator().encode(PointEncoding::Eip2537)),
+            [fp(g1_x), fp(g1_y)].concat()
+        );
+
+        let g2_x_c0 = "024aa2b2f08f0a91260805272dc51051c6e47ad4fa403b02b4510b647ae3d1770bac0326a805bbefd48056c8c121bdb8";
+        let g2_x_c1 = "13e02b6052719f607dacd3a088274f65596bd0d09920b61ab5da61bbdc7f5049334cf11213945d57e5ac7d055d042b7e";
+        let g2_y_c0 = "0ce5d527727d6e118cc9cdc6da2e351aadfd9baa8cbdd3a76d429a695160d12c923ac9cc3baca289e193548608b82801";
+        let g2_y_c1 = "0606c4a02ea734cc32acd2b02bc28b99cb3e287e85a763af267492ab572e99ab3f370d275cec1da1aaa9075ff05f79be";
+        assert_eq!(
+            hex::encode(G2Projective::generator().encode(PointEncoding::Eip2537)),
+            [fp(g2_x_c0), fp(g2_x_c1), fp(g2_y_c0), fp(g2_y_c1)].concat()
+        );
+
+        assert_eq!(
+            G1Projective::identity().encode(PointEncoding::Eip2537),
+            [0; 128]
+        );
+        assert_eq!(
+            G2Projective::identity().encode(PointEncoding::Eip2537),
+            [0; 256]
+        );
+    }
+
+    #[test]
+    fn public_keys_reencode_from_storage() {
+        let public_key = G2Projective::generator() * secret_key();
+        let stored = KeyGroup::to_uncompressed(&public_key);
+        for encoding in [
+            PointEncoding::Compressed,
+            PointEncoding::Uncompressed,
+            PointEncoding::Eip2537,
+        ] {
+            assert_eq!(
+                KeyScheme::MinimalSignatureSize
+                    .encode_public_key(&stored, encoding)
+                    .unwrap(),
+                public_key.encode(encoding)
+            );
+        }
+        assert_eq!(
+            hex::encode(
+                KeyScheme::MinimalSignatureSize
+                    .encode_public_key(&stored, PointEncoding::Compressed)
+                    .unwrap()
+            ),
+            MIN_SIG_PUBLIC_KEY
+        );
+    }
+}
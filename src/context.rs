@@ -1,16 +1,51 @@
-use crate::keygen_state_machine::BlsState;
+use crate::error::BlsError;
+use crate::metrics::metrics;
+use crate::policy::PolicyHook;
+use crate::session::SessionRouter;
+use crate::store::{CheckpointStore, ShareStore, store_encryption_key};
+use blueprint_sdk::alloy::primitives::{Address, U256};
+use blueprint_sdk::alloy::rpc::types::Filter;
+use blueprint_sdk::alloy::sol_types::SolEvent;
 use blueprint_sdk::clients::BlueprintServicesClient;
+use blueprint_sdk::clients::tangle::ITangle;
 use blueprint_sdk::contexts::tangle::TangleClientContext;
 use blueprint_sdk::crypto::k256::K256Ecdsa;
+use blueprint_sdk::networking::discovery::peers::VerificationIdentifierKey;
 use blueprint_sdk::networking::service_handle::NetworkServiceHandle;
 use blueprint_sdk::runner::config::BlueprintEnvironment;
-use blueprint_sdk::stores::local_database::LocalDatabase;
+use libp2p::PeerId;
+use round_based::PartyIndex;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::path::PathBuf;
-use std::sync::{Arc, OnceLock};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tracing::warn;
+use zeroize::Zeroize;
 
 /// The network protocol version for the BLS service
 pub(crate) const NETWORK_PROTOCOL: &str = "bls/gennaro/1.0.0";
 
+/// How long to wait for committee members to connect before a protocol starts
+pub(crate) const PARTY_READY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often to re-check committee connectivity while waiting
+const PARTY_READY_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often to check the chain for the completion of another job
+const JOB_COMPLETION_POLL_INTERVAL: Duration = Duration::from_secs(6);
+
+/// Environment variable holding how many seconds each DKG round may take
+/// before parties that have not sent their message are left out.
+pub const ROUND_TIMEOUT_ENV: &str = "BLS_ROUND_TIMEOUT_SECS";
+
+/// DKG round deadline used when [`ROUND_TIMEOUT_ENV`] is unset
+const DEFAULT_ROUND_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Round-based session identifier for a single protocol execution.
+pub(crate) fn session_id(deterministic_hash: &[u8; 32]) -> String {
+    format!("{NETWORK_PROTOCOL}/{}", hex::encode(deterministic_hash))
+}
+
 /// Global BLS context, initialized once at startup.
 static BLS_CTX: OnceLock<BlsContext> = OnceLock::new();
 
@@ -24,12 +59,92 @@ pub fn bls_ctx() -> &'static BlsContext {
 pub struct BlsContext {
     pub env: BlueprintEnvironment,
     pub network_backend: NetworkServiceHandle<K256Ecdsa>,
-    pub store: Arc<LocalDatabase<BlsState>>,
+    pub store: Arc<ShareStore>,
+    /// Checkpoints of keygen DKGs in progress
+    pub checkpoints: Arc<CheckpointStore>,
+    /// Routes incoming protocol messages to their session
+    pub sessions: Arc<SessionRouter>,
+    /// EVM address of the local operator
+    pub operator_address: Address,
+    /// Checks run on every signing request besides the key's own policy
+    pub policy_hooks: Vec<Arc<dyn PolicyHook>>,
+    /// How long each DKG round waits for the other parties
+    pub round_timeout: Duration,
+    /// Keys whose shares a refresh or reshare is replacing on this operator
+    key_updates: Arc<Mutex<HashSet<u64>>>,
+}
+
+/// Marks a key's shares as being replaced until dropped.
+pub(crate) struct KeyUpdateGuard {
+    key_updates: Arc<Mutex<HashSet<u64>>>,
+    keygen_call_id: u64,
+}
+
+impl Drop for KeyUpdateGuard {
+    fn drop(&mut self) {
+        self.key_updates
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.keygen_call_id);
+    }
+}
+
+/// The protocol committee: the service's registered operators, ordered by
+/// address, and the position of the local operator among them.
+#[derive(Clone, Debug)]
+pub(crate) struct Committee {
+    pub operators: Vec<Address>,
+    pub local_index: u16,
+}
+
+impl Committee {
+    /// Builds a committee from an ordered operator list.
+    pub fn new(operators: Vec<Address>, local_address: Address) -> Result<Self, BlsError> {
+        let local_index = operators
+            .iter()
+            .position(|op| *op == local_address)
+            .ok_or_else(|| {
+                BlsError::PeerSetMismatch(format!(
+                    "Local operator {local_address} is not in the committee"
+                ))
+            })? as u16;
+
+        Ok(Self {
+            operators,
+            local_index,
+        })
+    }
+
+    pub fn n(&self) -> u16 {
+        self.operators.len() as u16
+    }
+}
+
+/// Reads the DKG round deadline from [`ROUND_TIMEOUT_ENV`].
+fn round_timeout() -> Result<Duration, String> {
+    match std::env::var(ROUND_TIMEOUT_ENV) {
+        Ok(secs) => match secs.trim().parse::<u64>() {
+            Ok(secs) if secs > 0 => Ok(Duration::from_secs(secs)),
+            _ => Err(format!(
+                "{ROUND_TIMEOUT_ENV} must be a positive number of seconds, got {secs:?}"
+            )),
+        },
+        Err(_) => Ok(DEFAULT_ROUND_TIMEOUT),
+    }
 }
 
 impl BlsContext {
     /// Creates and globally initializes the BLS context.
     pub async fn init(env: &BlueprintEnvironment) -> Result<(), String> {
+        Self::init_with_policy_hooks(env, Vec::new()).await
+    }
+
+    /// Creates and globally initializes the BLS context, running
+    /// `policy_hooks` on every signing request.
+    pub async fn init_with_policy_hooks(
+        env: &BlueprintEnvironment,
+        policy_hooks: Vec<Arc<dyn PolicyHook>>,
+    ) -> Result<(), String> {
         let tangle_client = env.tangle_client().await.map_err(|e| e.to_string())?;
 
         let operators = tangle_client
@@ -45,22 +160,41 @@ impl BlsContext {
         let (_allowed_keys_tx, allowed_keys_rx) = crossbeam_channel::unbounded();
 
         let network_config = env
-            .libp2p_network_config::<K256Ecdsa>(NETWORK_PROTOCOL, false)
+            .libp2p_network_config::<K256Ecdsa>(NETWORK_PROTOCOL, true)
             .map_err(|e| e.to_string())?;
 
         let network_backend = env
             .libp2p_start_network(network_config, operator_keys, allowed_keys_rx)
             .map_err(|e| e.to_string())?;
 
-        let keystore_dir = PathBuf::from(&env.keystore_uri).join("bls.json");
-        let store = Arc::new(
-            LocalDatabase::open(keystore_dir).map_err(|e| format!("Failed to open store: {e}"))?,
-        );
+        let operator_id = tangle_client
+            .operator_id()
+            .await
+            .map_err(|e| e.to_string())?;
+        let operator_address = Address::from_raw_public_key(&operator_id[1..]);
+
+        let mut store_key = store_encryption_key(&network_backend.local_signing_key.0.to_bytes())?;
+        let keystore_dir = PathBuf::from(&env.keystore_uri);
+        let store = ShareStore::open(keystore_dir.join("bls.json"), &store_key);
+        let checkpoints =
+            CheckpointStore::open(keystore_dir.join("bls-checkpoints.json"), &store_key);
+        store_key.zeroize();
+        let store = Arc::new(store?);
+        let checkpoints = Arc::new(checkpoints?);
+
+        let sessions = SessionRouter::spawn(network_backend.clone());
+        let round_timeout = round_timeout()?;
 
         let ctx = BlsContext {
             env: env.clone(),
             network_backend,
+            sessions,
             store,
+            checkpoints,
+            operator_address,
+            policy_hooks,
+            round_timeout,
+            key_updates: Arc::default(),
         };
 
         BLS_CTX
@@ -69,11 +203,162 @@ impl BlsContext {
     }
 
     /// Returns the blueprint ID
-    pub fn blueprint_id(&self) -> Result<u64, String> {
+    pub fn blueprint_id(&self) -> Result<u64, BlsError> {
         self.env
             .protocol_settings
             .tangle()
             .map(|c| c.blueprint_id)
-            .map_err(|err| format!("Blueprint ID not found: {err}"))
+            .map_err(|err| BlsError::Chain(format!("Blueprint ID not found: {err}")))
+    }
+
+    /// Claims the shares of key `keygen_call_id` for a job replacing them.
+    /// Fails while another such job runs on the key, as the two would
+    /// leave operators with shares of different sharings.
+    pub(crate) fn lock_key(&self, keygen_call_id: u64) -> Result<KeyUpdateGuard, BlsError> {
+        let mut key_updates = self.key_updates.lock().unwrap_or_else(|e| e.into_inner());
+        if !key_updates.insert(keygen_call_id) {
+            return Err(BlsError::KeyBusy { keygen_call_id });
+        }
+        Ok(KeyUpdateGuard {
+            key_updates: self.key_updates.clone(),
+            keygen_call_id,
+        })
+    }
+
+    /// Fetches the service's registered operators from the chain, ordered by
+    /// operator address.
+    pub(crate) async fn service_operators(&self) -> Result<Vec<Address>, BlsError> {
+        let operators = self
+            .env
+            .tangle_client()
+            .await
+            .map_err(|e| BlsError::Chain(e.to_string()))?
+            .get_operators()
+            .await
+            .map_err(|e| BlsError::Chain(e.to_string()))?;
+
+        Ok(operators.into_keys().collect())
+    }
+
+    /// Fetches the service's registered operators from the chain and returns
+    /// them as a committee, ordered by operator address.
+    pub(crate) async fn committee(&self) -> Result<Committee, BlsError> {
+        Committee::new(self.service_operators().await?, self.operator_address)
+    }
+
+    /// Waits until the chain reports job `call_id` of the service as
+    /// completed, watching from `from_block`. Returns whether it did before
+    /// `timeout`.
+    pub(crate) async fn wait_for_job_completion(
+        &self,
+        service_id: u64,
+        call_id: u64,
+        from_block: u64,
+        timeout: Duration,
+    ) -> Result<bool, BlsError> {
+        let client = self
+            .env
+            .tangle_client()
+            .await
+            .map_err(|e| BlsError::Chain(e.to_string()))?;
+        let filter = Filter::new()
+            .address(client.tangle_address())
+            .event_signature(ITangle::JobCompleted::SIGNATURE_HASH)
+            .topic1(U256::from(service_id))
+            .topic2(U256::from(call_id))
+            .from_block(from_block);
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        loop {
+            match client.get_logs(&filter).await {
+                Ok(logs) if !logs.is_empty() => return Ok(true),
+                Ok(_) => {}
+                Err(e) => warn!("Failed to fetch completion of job {call_id}: {e}"),
+            }
+
+            if tokio::time::Instant::now() >= deadline {
+                return Ok(false);
+            }
+
+            tokio::time::sleep(JOB_COMPLETION_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Waits until at least `required` other committee members are connected
+    /// and returns the party index to peer ID mapping of those reachable.
+    pub(crate) async fn wait_for_parties(
+        &self,
+        committee: &Committee,
+        required: usize,
+    ) -> Result<HashMap<PartyIndex, PeerId>, BlsError> {
+        self.wait_for_members(committee, |_| true, required).await
+    }
+
+    /// Waits until the other `holders` of a key's shares in the committee
+    /// are connected and returns their party index to peer ID mapping.
+    pub(crate) async fn wait_for_holders(
+        &self,
+        committee: &Committee,
+        holders: &BTreeSet<PartyIndex>,
+    ) -> Result<HashMap<PartyIndex, PeerId>, BlsError> {
+        let required = holders.len().saturating_sub(1);
+        self.wait_for_members(committee, |idx| holders.contains(&idx), required)
+            .await
+    }
+
+    /// Waits until at least `required` other committee members selected by
+    /// `member` are connected.
+    async fn wait_for_members(
+        &self,
+        committee: &Committee,
+        member: impl Fn(PartyIndex) -> bool,
+        required: usize,
+    ) -> Result<HashMap<PartyIndex, PeerId>, BlsError> {
+        let deadline = tokio::time::Instant::now() + PARTY_READY_TIMEOUT;
+
+        loop {
+            let connected = self.network_backend.peers();
+            let mut parties = HashMap::new();
+            let mut missing = Vec::new();
+
+            for (idx, operator) in committee.operators.iter().enumerate() {
+                let idx = idx as PartyIndex;
+                if !member(idx) {
+                    continue;
+                }
+                if idx == committee.local_index {
+                    parties.insert(idx, self.network_backend.local_peer_id);
+                    continue;
+                }
+
+                let key = VerificationIdentifierKey::EvmAddress(*operator);
+                match self
+                    .network_backend
+                    .peer_manager
+                    .get_peer_id_from_verification_id_key(&key)
+                    .filter(|peer_id| connected.contains(peer_id))
+                {
+                    Some(peer_id) => {
+                        parties.insert(idx, peer_id);
+                    }
+                    None => missing.push(*operator),
+                }
+            }
+
+            metrics().connected_peers.set(parties.len() as i64 - 1);
+            metrics().committee_size.set(committee.n() as i64);
+            if parties.len() > required {
+                return Ok(parties);
+            }
+
+            if tokio::time::Instant::now() >= deadline {
+                return Err(BlsError::PeerSetMismatch(format!(
+                    "Timed out waiting for committee peers: {} of {required} connected, missing {missing:?}",
+                    parties.len() - 1
+                )));
+            }
+
+            tokio::time::sleep(PARTY_READY_POLL_INTERVAL).await;
+        }
     }
 }
@@ -0,0 +1,341 @@
+use crate::ciphersuite::CiphersuiteError;
+use crate::keygen::KeygenError;
+use crate::signing::SigningError;
+use crate::store::StoreError;
+use blueprint_sdk::alloy::primitives::Address;
+use blueprint_sdk::alloy::sol_types::SolValue;
+use blueprint_sdk::tangle::extract::TangleResult;
+use blueprint_sdk::{IntoJobResult, JobCall, JobResult};
+use round_based::PartyIndex;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+use tracing::warn;
+
+/// Metadata key of the [`BlsError`] code on the results of failed jobs.
+pub const ERROR_CODE_METADATA_KEY: &str = "X-BLS-ERROR-CODE";
+
+/// Error returned by every job.
+///
+/// [`ReportFailureLayer`] submits it on chain in the `code` and `message`
+/// fields of the job's result, as the consumer drops failed job results.
+/// Codes are stable: variants may be added, but a code is never reused.
+#[derive(Debug, thiserror::Error)]
+pub enum BlsError {
+    #[error("[1] Invalid request: {0}")]
+    InvalidRequest(String),
+
+    #[error("[2] Key {keygen_call_id} not found")]
+    KeyNotFound { keygen_call_id: u64 },
+
+    #[error("[3] Key {keygen_call_id} was retired at block {block}")]
+    KeyRetired { keygen_call_id: u64, block: u64 },
+
+    #[error("[4] Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("[5] Refused by signing policy: {0}")]
+    PolicyRefused(String),
+
+    #[error("[6] Peer set mismatch: {0}")]
+    PeerSetMismatch(String),
+
+    #[error("[7] Round {round} timed out waiting for operators {missing_operators:?}")]
+    Timeout {
+        round: u8,
+        missing_operators: Vec<Address>,
+    },
+
+    #[error(
+        "[8] Only {valid} valid signature shares, need t={t}; invalid shares from operators {offenders:?}"
+    )]
+    BadShares {
+        valid: usize,
+        t: u16,
+        offenders: Vec<Address>,
+    },
+
+    #[error("[9] Operator {operator} misbehaved in round {round}: {reason}")]
+    Misbehavior {
+        operator: Address,
+        round: u8,
+        reason: String,
+    },
+
+    #[error("[10] Protocol error: {0}")]
+    Protocol(String),
+
+    #[error("[11] {0}")]
+    Store(#[from] StoreError),
+
+    #[error("[12] Chain error: {0}")]
+    Chain(String),
+
+    #[error("[13] Internal error: {0}")]
+    Internal(String),
+
+    #[error("[14] Key {keygen_call_id} already has its shares replaced by another job")]
+    KeyBusy { keygen_call_id: u64 },
+}
+
+impl BlsError {
+    /// Stable numeric code of the error.
+    pub fn code(&self) -> u16 {
+        match self {
+            BlsError::InvalidRequest(_) => 1,
+            BlsError::KeyNotFound { .. } => 2,
+            BlsError::KeyRetired { .. } => 3,
+            BlsError::Unauthorized(_) => 4,
+            BlsError::PolicyRefused(_) => 5,
+            BlsError::PeerSetMismatch(_) => 6,
+            BlsError::Timeout { .. } => 7,
+            BlsError::BadShares { .. } => 8,
+            BlsError::Misbehavior { .. } => 9,
+            BlsError::Protocol(_) => 10,
+            BlsError::Store(_) => 11,
+            BlsError::Chain(_) => 12,
+            BlsError::Internal(_) => 13,
+            BlsError::KeyBusy { .. } => 14,
+        }
+    }
+
+    /// Maps a DKG error, naming parties by their operator in `operators`.
+    pub(crate) fn keygen(err: KeygenError, operators: &[Address]) -> Self {
+        match err {
+            KeygenError::Misbehavior {
+                party,
+                round,
+                reason,
+            } => BlsError::Misbehavior {
+                operator: operators
+                    .get(usize::from(party))
+                    .copied()
+                    .unwrap_or_default(),
+                round,
+                reason,
+            },
+            KeygenError::Timeout {
+                round,
+                missing_parties,
+            } => BlsError::Timeout {
+                round,
+                missing_operators: operator_addresses(operators, &missing_parties),
+            },
+            KeygenError::MpcError(_) | KeygenError::DeliveryError(_) => {
+                BlsError::Protocol(err.to_string())
+            }
+        }
+    }
+
+    /// Maps a signing error, naming parties by their operator in
+    /// `operators`.
+    pub(crate) fn signing(err: SigningError, operators: &[Address]) -> Self {
+        match err {
+            SigningError::Unauthorized { .. } => BlsError::Unauthorized(err.to_string()),
+            SigningError::MessagePrefixMismatch { .. }
+            | SigningError::MessageTooLong { .. }
+            | SigningError::RateLimited { .. }
+            | SigningError::MissingSlotDomain { .. }
+            | SigningError::SlashableMessage { .. }
+            | SigningError::SlotTooOld { .. } => BlsError::PolicyRefused(err.to_string()),
+            SigningError::PolicyRefused(reason) => BlsError::PolicyRefused(reason),
+            SigningError::InvalidShares {
+                valid,
+                t,
+                offenders,
+            } => BlsError::BadShares {
+                valid,
+                t,
+                offenders: operator_addresses(operators, &offenders),
+            },
+            SigningError::KeyRetrievalError(_) => BlsError::Internal(err.to_string()),
+            SigningError::MpcError(_) => BlsError::Protocol(err.to_string()),
+        }
+    }
+}
+
+impl From<CiphersuiteError> for BlsError {
+    fn from(err: CiphersuiteError) -> Self {
+        BlsError::InvalidRequest(err.to_string())
+    }
+}
+
+/// Job result able to report a failure of its job on chain.
+pub trait JobFailure: SolValue + Send + 'static {
+    /// Result carrying the code and message of `err`, without any output.
+    fn failed(err: &BlsError) -> Self;
+}
+
+/// Turns the failures of the jobs routed through it into `R` results
+/// carrying the [`BlsError`], so they reach the chain. Requests the job's
+/// extractors reject are reported as [`BlsError::InvalidRequest`].
+pub struct ReportFailureLayer<R> {
+    _result: PhantomData<fn() -> R>,
+}
+
+impl<R> ReportFailureLayer<R> {
+    pub fn new() -> Self {
+        Self {
+            _result: PhantomData,
+        }
+    }
+}
+
+impl<R> Default for ReportFailureLayer<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R> Clone for ReportFailureLayer<R> {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
+impl<S, R> Layer<S> for ReportFailureLayer<R> {
+    type Service = ReportFailure<S, R>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ReportFailure {
+            inner,
+            _result: PhantomData,
+        }
+    }
+}
+
+pub struct ReportFailure<S, R> {
+    inner: S,
+    _result: PhantomData<fn() -> R>,
+}
+
+impl<S: Clone, R> Clone for ReportFailure<S, R> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            _result: PhantomData,
+        }
+    }
+}
+
+impl<S, R> Service<JobCall> for ReportFailure<S, R>
+where
+    S: Service<JobCall, Response = Option<JobResult>>,
+    S::Error: Send + 'static,
+    S::Future: Send + 'static,
+    R: JobFailure,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, call: JobCall) -> Self::Future {
+        let job = call.job_id();
+        let future = self.inner.call(call);
+        Box::pin(async move {
+            let result = future.await?;
+            let Some(JobResult::Err(err)) = result else {
+                return Ok(result);
+            };
+            let inner = err.into_inner();
+            let err = match inner.downcast_ref::<BlsError>() {
+                Some(err) => err,
+                None => &BlsError::InvalidRequest(inner.to_string()),
+            };
+            warn!("Job {job} failed: {err}");
+
+            let mut result = TangleResult(R::failed(err)).into_job_result();
+            if let Some(JobResult::Ok { head, .. }) = &mut result {
+                head.metadata.insert(ERROR_CODE_METADATA_KEY, err.code());
+            }
+            Ok(result)
+        })
+    }
+}
+
+/// Maps party indices of a committee to operator addresses.
+pub(crate) fn operator_addresses(operators: &[Address], parties: &[PartyIndex]) -> Vec<Address> {
+    parties
+        .iter()
+        .filter_map(|party| operators.get(usize::from(*party)).copied())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{KeygenResult, SignResult};
+    use blueprint_sdk::core::error::BoxError;
+    use std::future::{Ready, ready};
+    use std::sync::Arc;
+
+    /// Job service failing every call with `err`
+    #[derive(Clone)]
+    struct Failing(Arc<dyn Fn() -> BoxError + Send + Sync>);
+
+    impl Service<JobCall> for Failing {
+        type Response = Option<JobResult>;
+        type Error = BoxError;
+        type Future = Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _call: JobCall) -> Self::Future {
+            let err = blueprint_sdk::core::error::Error::new((self.0)());
+            ready(Ok(Some(JobResult::Err(err))))
+        }
+    }
+
+    /// Body and metadata code of the result reporting `err`
+    async fn report<R: JobFailure>(err: fn() -> BoxError) -> (Vec<u8>, u16) {
+        let mut service = ReportFailureLayer::<R>::new().layer(Failing(Arc::new(err)));
+        let Some(JobResult::Ok { head, body }) = service.call(JobCall::empty()).await.unwrap()
+        else {
+            panic!("failure not reported as a result");
+        };
+        let code = head.metadata.get(ERROR_CODE_METADATA_KEY).unwrap();
+        (body.to_vec(), u16::try_from(code).unwrap())
+    }
+
+    #[tokio::test]
+    async fn reports_failures_in_the_result() {
+        let (body, code) = report::<KeygenResult>(|| {
+            BlsError::Misbehavior {
+                operator: Address::repeat_byte(7),
+                round: 2,
+                reason: "bad share".into(),
+            }
+            .into()
+        })
+        .await;
+        let result = KeygenResult::abi_decode(&body).unwrap();
+        assert_eq!(code, 9);
+        assert_eq!(result.code, 9);
+        assert_eq!(
+            result.message,
+            format!(
+                "[9] Operator {} misbehaved in round 2: bad share",
+                Address::repeat_byte(7)
+            )
+        );
+        assert_eq!(result.culprit, Address::repeat_byte(7));
+        assert_eq!(result.failed_round, 2);
+        assert_eq!(result.reason, "bad share");
+        assert!(result.public_key.is_empty());
+
+        let (body, code) = report::<SignResult>(|| "bad argument".into()).await;
+        let result = SignResult::abi_decode(&body).unwrap();
+        assert_eq!(code, 1);
+        assert_eq!(result.code, 1);
+        assert_eq!(result.message, "[1] Invalid request: bad argument");
+        assert!(result.signature.is_empty());
+    }
+}
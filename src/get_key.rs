@@ -0,0 +1,50 @@
+use crate::ciphersuite::PointEncoding;
+use crate::context::bls_ctx;
+use crate::error::BlsError;
+use crate::{GetKeyRequest, GetKeyResult};
+use blueprint_sdk::info;
+use blueprint_sdk::tangle::extract::{CallId, Caller, TangleArg, TangleResult};
+
+/// Returns the metadata of a previously generated key without running a
+/// protocol.
+///
+/// Each operator answers from its own store: the public key, threshold,
+/// committee and scheme of the key from `keygen_call_id`, and the block its
+/// shares were created at.
+pub async fn get_key(
+    CallId(call_id): CallId,
+    Caller(_caller): Caller,
+    TangleArg(request): TangleArg<GetKeyRequest>,
+) -> Result<TangleResult<GetKeyResult>, BlsError> {
+    let ctx = bls_ctx();
+    let keygen_call_id = request.keygen_call_id;
+    let encoding = PointEncoding::try_from(request.encoding)?;
+
+    let (_, state) = ctx
+        .store
+        .find_by_call_id(keygen_call_id)?
+        .ok_or(BlsError::KeyNotFound { keygen_call_id })?;
+
+    let public_key = state
+        .uncompressed_pk
+        .as_deref()
+        .ok_or_else(|| BlsError::Internal("Public key missing from key entry".into()))?;
+    let public_key = state.scheme.encode_public_key(public_key, encoding)?;
+
+    let n = u16::try_from(state.operators.len()).map_err(|_| {
+        BlsError::Internal(format!("Key entry has {} operators", state.operators.len()))
+    })?;
+
+    info!("Answering key query {call_id} for keygen_call_id={keygen_call_id}");
+
+    Ok(TangleResult(GetKeyResult {
+        public_key: public_key.into(),
+        t: state.t,
+        n,
+        operators: state.operators,
+        scheme: state.scheme.into(),
+        created_at_block: state.created_at_block,
+        retired_at_block: state.retired_at_block.unwrap_or_default(),
+        ..Default::default()
+    }))
+}
@@ -1,68 +1,128 @@
+use crate::JOB_KEYGEN;
 use crate::KeygenRequest;
 use crate::KeygenResult;
+use crate::ciphersuite::{KeyScheme, PointEncoding, SigningDomain};
 use crate::context::bls_ctx;
-use crate::keygen_state_machine::KeygenMsg;
-use blueprint_sdk::crypto::k256::K256Ecdsa;
-use blueprint_sdk::info;
-use blueprint_sdk::networking::round_based_compat::RoundBasedNetworkAdapter;
-use blueprint_sdk::tangle::extract::{Caller, TangleArg, TangleResult};
+use crate::error::BlsError;
+use crate::keygen_state_machine::{BlsState, DkgCheckpoint, KeygenMsg, compressed_public_key};
+use crate::session::SessionNetwork;
+use crate::signing_state_machine::SigningMsg;
+use blueprint_sdk::alloy::primitives::Address;
+use blueprint_sdk::tangle::extract::{
+    BlockNumber, CallId, Caller, ServiceId, TangleArg, TangleResult,
+};
+use blueprint_sdk::{JobCall, JobId, info, warn};
+use futures::{Stream, StreamExt, TryStreamExt};
+use libp2p::PeerId;
 use round_based::PartyIndex;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::future::ready;
+use tracing::Instrument;
 
 const KEYGEN_SALT: &str = "bls-keygen";
+const POP_SALT: &str = "bls-keygen-pop";
 
 /// Runs a distributed key generation (DKG) process using the BLS protocol.
 ///
-/// Extracts threshold `t` from the on-chain request, runs the Gennaro DKG
-/// protocol via round-based networking, and returns the aggregated public key.
+/// Extracts threshold `t` and the key scheme from the on-chain request, runs
+/// the Gennaro DKG protocol in the scheme's public key group among the
+/// service's operators via round-based networking, and returns the group
+/// public key.
+/// The key is stored under the job's call ID, which signing requests
+/// reference as `keygen_call_id`, with the job's caller as its owner. If
+/// requested, the operators then sign the new key's proof of possession in
+/// one more round. If keygen aborts because an operator misbehaved, the
+/// result names that operator instead. Operators that miss a round's
+/// deadline are left out as long as `t` parties remain. The DKG is
+/// checkpointed after every round, so an operator restarting mid-DKG
+/// resumes it when the job is picked up again. A call whose key is stored
+/// already is refused, so replaying it cannot replace the key's shares.
 pub async fn keygen(
-    Caller(_caller): Caller,
+    ServiceId(service_id): ServiceId,
+    CallId(call_id): CallId,
+    BlockNumber(block_number): BlockNumber,
+    Caller(caller): Caller,
     TangleArg(request): TangleArg<KeygenRequest>,
-) -> Result<TangleResult<KeygenResult>, String> {
+) -> Result<TangleResult<KeygenResult>, BlsError> {
     let ctx = bls_ctx();
     let t = request.t;
+    let scheme = KeyScheme::try_from(request.scheme)?;
+    let encoding = PointEncoding::try_from(request.encoding)?;
 
-    // Get party info from connected peers
-    let mut all_peers = ctx.network_backend.peers();
-    let local_peer_id = ctx.network_backend.local_peer_id;
-    if !all_peers.contains(&local_peer_id) {
-        all_peers.push(local_peer_id);
+    // The committee is the service's registered operators, not whoever is
+    // currently connected, so every party agrees on `n` and the indices.
+    let committee = ctx.committee().await?;
+    let n = committee.n();
+    let i = committee.local_index;
+
+    if t == 0 || t > n {
+        return Err(BlsError::InvalidRequest(format!(
+            "Invalid threshold t={t} for n={n}"
+        )));
     }
-    all_peers.sort();
 
-    let n = all_peers.len() as u16;
-    let i = all_peers
-        .iter()
-        .position(|p| *p == local_peer_id)
-        .ok_or_else(|| "Local peer not found in peer list".to_string())? as u16;
+    // A replayed call must not replace the share of a published key
+    if ctx.store.find_by_call_id(call_id)?.is_some() {
+        return Err(BlsError::InvalidRequest(format!(
+            "Key {call_id} was generated already"
+        )));
+    }
 
-    let parties: HashMap<PartyIndex, libp2p::PeerId> = all_peers
-        .into_iter()
-        .enumerate()
-        .map(|(idx, peer_id)| (idx as PartyIndex, peer_id))
-        .collect();
+    // Every operator must take part in the DKG
+    let parties = ctx.wait_for_parties(&committee, usize::from(n) - 1).await?;
 
     let blueprint_id = ctx.blueprint_id()?;
-    let call_id = 0u64; // Deterministic from on-chain context
 
     let (meta_hash, deterministic_hash) =
         crate::compute_deterministic_hashes(n, blueprint_id, call_id, KEYGEN_SALT);
 
     info!(
-        "Starting BLS Keygen for party {i}, n={n}, t={t}, eid={}",
+        "Starting BLS Keygen for party {i}, n={n}, t={t}, scheme={scheme:?}, call_id={call_id}, eid={}",
         hex::encode(deterministic_hash)
     );
 
-    let network = RoundBasedNetworkAdapter::<KeygenMsg, K256Ecdsa>::new(
+    let span = crate::telemetry::job_span("keygen", service_id, call_id, &deterministic_hash, i);
+
+    let session = crate::context::session_id(&deterministic_hash);
+    let network = SessionNetwork::<KeygenMsg>::new(
+        &ctx.sessions,
         ctx.network_backend.clone(),
-        i,
         &parties,
-        crate::context::NETWORK_PROTOCOL,
+        session.clone(),
     );
 
     let party = round_based::party::MpcParty::connected(network);
 
-    let output = crate::keygen_state_machine::bls_keygen_protocol(party, i, t, n, call_id).await?;
+    // A DKG interrupted by a restart resumes from its checkpoint
+    let resume = ctx.checkpoints.get(&session)?;
+    let save = {
+        let checkpoints = ctx.checkpoints.clone();
+        let session = session.clone();
+        Box::new(move |checkpoint: &DkgCheckpoint| {
+            checkpoints
+                .set(&session, call_id, block_number, checkpoint)
+                .map_err(|e| KeygenError::MpcError(format!("Failed to save checkpoint: {e}")))
+        })
+    };
+
+    let result = crate::keygen_state_machine::bls_keygen_protocol(
+        party,
+        i,
+        t,
+        n,
+        scheme,
+        call_id,
+        ctx.round_timeout,
+        resume,
+        save,
+    )
+    .instrument(span.clone())
+    .await;
+    if result.is_err() {
+        ctx.checkpoints.remove(&session)?;
+    }
+
+    let mut output = result.map_err(|err| BlsError::keygen(err, &committee.operators))?;
 
     info!(
         "Ending BLS Keygen for party {i}, n={n}, t={t}, eid={}",
@@ -71,35 +131,210 @@ pub async fn keygen(
 
     let public_key = output
         .uncompressed_pk
-        .clone()
-        .ok_or_else(|| "Public key missing from keygen output".to_string())?;
+        .as_deref()
+        .ok_or_else(|| BlsError::Internal("Public key missing from keygen output".into()))?;
+    let public_key = scheme.encode_public_key(public_key, encoding)?;
+
+    output.operators = committee.operators;
+    output.created_at_block = block_number;
+    output.owner = Address::from(caller);
+    output.policy = request.policy.into();
 
     // Store the results
     let store_key = hex::encode(meta_hash);
-    let _ = ctx.store.set(&store_key, output);
+    ctx.store.set(&store_key, output.clone())?;
+    ctx.checkpoints.remove(&session)?;
+
+    // The key is stored already, so on failure JOB_PROVE_POSSESSION can
+    // produce the proof later
+    let proof_of_possession = if request.proof_of_possession {
+        let (_, pop_hash) = crate::compute_deterministic_hashes(n, blueprint_id, call_id, POP_SALT);
+        let proof = prove_possession(&output, &parties, i, n, pop_hash)
+            .instrument(span)
+            .await?;
+        scheme.encode_signature(&proof, encoding)?
+    } else {
+        Vec::new()
+    };
 
     Ok(TangleResult(KeygenResult {
         public_key: public_key.into(),
+        proof_of_possession: proof_of_possession.into(),
+        ..Default::default()
     }))
 }
 
+/// Signs the compressed public key of the freshly generated `state` under
+/// the POP ciphersuite's proof DST with the keygen's parties.
+async fn prove_possession(
+    state: &BlsState,
+    parties: &HashMap<PartyIndex, PeerId>,
+    i: PartyIndex,
+    n: u16,
+    deterministic_hash: [u8; 32],
+) -> Result<Vec<u8>, BlsError> {
+    let ctx = bls_ctx();
+    let public_key =
+        compressed_public_key(state).map_err(|e| BlsError::keygen(e, &state.operators))?;
+    let domain = SigningDomain::proof_of_possession(state.scheme);
+
+    info!(
+        "Starting BLS proof of possession for party {i}, n={n}, eid={}",
+        hex::encode(deterministic_hash)
+    );
+
+    let network = SessionNetwork::<SigningMsg>::new(
+        &ctx.sessions,
+        ctx.network_backend.clone(),
+        parties,
+        crate::context::session_id(&deterministic_hash),
+    );
+
+    let party = round_based::party::MpcParty::connected(network);
+
+    let mut output = crate::signing_state_machine::bls_signing_protocol(
+        party,
+        i,
+        n,
+        state,
+        &domain,
+        &[public_key],
+    )
+    .await
+    .map_err(|e| BlsError::signing(e, &state.operators))?;
+
+    if !output.invalid_shares.is_empty() {
+        warn!(
+            "BLS proof of possession dropped invalid signature shares from parties {:?}",
+            output.invalid_shares
+        );
+    }
+
+    output
+        .signatures
+        .pop()
+        .ok_or_else(|| BlsError::Internal("Proof of possession not found in signing output".into()))
+}
+
+/// Yields the calls of the keygen jobs `interrupted` by a restart from
+/// `producer`, each once, so their DKGs resume from their checkpoints, and
+/// ends after the last of them. `producer` must start at the earliest of
+/// their blocks.
+pub fn resumed_keygens<S, E>(
+    producer: S,
+    interrupted: HashSet<u64>,
+) -> impl Stream<Item = Result<JobCall, E>>
+where
+    S: Stream<Item = Result<JobCall, E>>,
+{
+    let count = interrupted.len();
+    let mut resumed = HashSet::new();
+    producer
+        .try_filter(move |call| {
+            ready(
+                keygen_call_id(call).is_some_and(|call_id| {
+                    interrupted.contains(&call_id) && resumed.insert(call_id)
+                }),
+            )
+        })
+        .take(count)
+}
+
+/// Drops the calls of the keygen jobs `interrupted` by a restart from
+/// `producer`, as [`resumed_keygens`] yields them.
+pub fn skip_resumed_keygens<S, E>(
+    producer: S,
+    interrupted: HashSet<u64>,
+) -> impl Stream<Item = Result<JobCall, E>>
+where
+    S: Stream<Item = Result<JobCall, E>>,
+{
+    producer.try_filter(move |call| {
+        ready(!keygen_call_id(call).is_some_and(|call_id| interrupted.contains(&call_id)))
+    })
+}
+
+/// Call ID of `call` if it is a keygen call.
+fn keygen_call_id(call: &JobCall) -> Option<u64> {
+    if call.job_id() != JobId::from(JOB_KEYGEN) {
+        return None;
+    }
+    u64::try_from(call.metadata().get(CallId::METADATA_KEY)?).ok()
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum KeygenError {
-    #[error("Failed to serialize data: {0}")]
-    SerializationError(String),
-
     #[error("MPC protocol error: {0}")]
     MpcError(String),
 
-    #[error("Context error: {0}")]
-    ContextError(String),
-
     #[error("Delivery error: {0}")]
     DeliveryError(String),
+
+    #[error("Party {party} misbehaved in round {round}: {reason}")]
+    Misbehavior {
+        party: PartyIndex,
+        round: u8,
+        reason: String,
+    },
+
+    #[error("Round {round} timed out waiting for parties {missing_parties:?}")]
+    Timeout {
+        round: u8,
+        missing_parties: Vec<PartyIndex>,
+    },
 }
 
-impl From<KeygenError> for String {
-    fn from(err: KeygenError) -> Self {
-        err.to_string()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JOB_SIGN;
+    use futures::stream;
+
+    fn call(job: u8, call_id: u64) -> Result<JobCall, ()> {
+        let mut call = JobCall::new(job, blueprint_sdk::Bytes::new());
+        call.metadata_mut().insert(CallId::METADATA_KEY, call_id);
+        Ok(call)
+    }
+
+    fn ids(calls: Vec<Result<JobCall, ()>>) -> Vec<(JobId, u64)> {
+        calls
+            .into_iter()
+            .map(|call| {
+                let call = call.unwrap();
+                let call_id = u64::try_from(call.metadata().get(CallId::METADATA_KEY).unwrap());
+                (call.job_id(), call_id.unwrap())
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn resumes_only_interrupted_keygens() {
+        let calls = || {
+            stream::iter([
+                call(JOB_KEYGEN, 1),
+                call(JOB_SIGN, 2),
+                call(JOB_KEYGEN, 3),
+                call(JOB_KEYGEN, 3),
+                call(JOB_KEYGEN, 4),
+                call(JOB_KEYGEN, 5),
+            ])
+        };
+        let interrupted = HashSet::from([3, 4]);
+
+        let resumed = resumed_keygens(calls(), interrupted.clone());
+        assert_eq!(
+            ids(resumed.collect().await),
+            [(JOB_KEYGEN.into(), 3), (JOB_KEYGEN.into(), 4)]
+        );
+
+        let live = skip_resumed_keygens(calls(), interrupted);
+        assert_eq!(
+            ids(live.collect().await),
+            [
+                (JOB_KEYGEN.into(), 1),
+                (JOB_SIGN.into(), 2),
+                (JOB_KEYGEN.into(), 5)
+            ]
+        );
     }
 }
@@ -1,70 +1,147 @@
-use blsful::inner_types::{G1Projective, Scalar};
-use gennaro_dkg::vsss_rs::IdentifierPrimeField;
-use gennaro_dkg::{Parameters, SecretParticipant};
+use blsful::inner_types::{Field, G1Projective, G2Projective, GroupEncoding, Scalar};
+use blueprint_sdk::alloy::primitives::Address;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use gennaro_dkg::SecretShare as DkgShare;
+use gennaro_dkg::elliptic_curve_tools::SumOfProducts;
+use gennaro_dkg::rand_core::{OsRng, RngCore};
+use gennaro_dkg::vsss_rs::{IdentifierPrimeField, Share};
+use gennaro_dkg::{
+    GroupHasher, Parameters, Participant, ParticipantImpl, RefreshParticipant, SecretParticipant,
+};
 use round_based::MessageDestination;
-use round_based::rounds_router::{RoundsRouter, simple_store::RoundInput};
-use round_based::{Delivery, Mpc, MpcParty, PartyIndex, ProtocolMessage};
+use round_based::{
+    Delivery, Incoming, MessageType, Mpc, MpcParty, Outgoing, PartyIndex, ProtocolMessage,
+    RoundMessage,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::num::NonZeroUsize;
-use tracing::info;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+use zeroize::Zeroize;
 
+use crate::ciphersuite::{KeyGroup, KeyScheme};
+use crate::error::BlsError;
 use crate::keygen::KeygenError;
+use crate::metrics::metrics;
+use crate::policy::{PolicyRecord, SigningPolicy};
+use crate::secret::SecretShare;
 
 /// State persisted after keygen, needed for signing.
-/// Stores the secret key scalar and aggregated public key as raw bytes.
+/// Stores the secret key scalar, group public key and verification shares as raw bytes.
 #[derive(Default, Serialize, Deserialize, Clone, Debug)]
 pub struct BlsState {
     /// Secret key scalar bytes (32 bytes, big-endian)
-    pub secret_key_bytes: Option<Vec<u8>>,
-    /// Aggregated uncompressed public key bytes (97 bytes, milagro format)
+    pub secret_key_bytes: Option<SecretShare>,
+    /// Secret key scalar sealed for storage (nonce || ciphertext)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encrypted_secret_key: Option<Vec<u8>>,
+    /// Group public key from the DKG, uncompressed with a `0x04` prefix
+    /// (97 bytes in G1, 193 bytes in G2)
     pub uncompressed_pk: Option<Vec<u8>>,
+    /// Groups the key and its signatures live in
+    #[serde(default)]
+    pub scheme: KeyScheme,
+    /// Compressed verification shares (`g * share_i`) in the public key
+    /// group, keyed by party index
+    #[serde(default)]
+    pub verification_shares: BTreeMap<u16, Vec<u8>>,
+    /// Committee operators in party index order, fixed at keygen
+    #[serde(default)]
+    pub operators: Vec<Address>,
+    /// Caller of the keygen job, who may sign with, manage and retire the key
+    #[serde(default)]
+    pub owner: Address,
+    /// Callers the owner delegated signing with the key to
+    #[serde(default)]
+    pub signers: Vec<Address>,
+    /// Rules messages must satisfy before this operator signs them
+    #[serde(default)]
+    pub policy: SigningPolicy,
+    /// What the policy remembers of earlier signing requests
+    #[serde(default)]
+    pub policy_record: PolicyRecord,
     /// The call_id of the keygen job
     pub call_id: u64,
+    /// Block of the keygen or reshare job that created this set of shares
+    #[serde(default)]
+    pub created_at_block: u64,
+    /// Block the key was retired at. A retired entry is a tombstone without
+    /// a secret share.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retired_at_block: Option<u64>,
     /// Threshold
     pub t: u16,
 }
 
-/// Messages for the BLS keygen protocol.
-/// Rounds 1-4: gennaro-dkg protocol (run/receive pattern)
-/// Round 5: milagro public key share broadcast + aggregation
+impl BlsState {
+    /// Checks that `caller` owns the key, for jobs only the owner may run.
+    pub fn check_owner(&self, caller: Address) -> Result<(), BlsError> {
+        if self.owner.is_zero() {
+            return Err(BlsError::Unauthorized(format!(
+                "Key {} has no recorded owner",
+                self.call_id
+            )));
+        }
+        if self.owner != caller {
+            return Err(BlsError::Unauthorized(format!(
+                "Only the key owner {} may manage key {}",
+                self.owner, self.call_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether `caller` may have the key sign: its owner or a delegated
+    /// signer. Keys from before owners were recorded sign for anyone.
+    pub fn is_authorized_signer(&self, caller: Address) -> bool {
+        self.owner.is_zero() || self.owner == caller || self.signers.contains(&caller)
+    }
+}
+
+/// Messages for the BLS keygen protocol: the four gennaro-dkg rounds
+/// (run/receive pattern) and requests to send them again.
 #[derive(ProtocolMessage, Serialize, Deserialize, Clone)]
 pub enum KeygenMsg {
     DkgRound1(DkgRound1Msg),
     DkgRound2(DkgRound2Msg),
     DkgRound3(DkgRound3Msg),
     DkgRound4(DkgRound4Msg),
-    PkShareBroadcast(PkShareMsg),
+    Resend(ResendMsg),
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 pub struct DkgRound1Msg {
     pub source: u16,
     pub payload: Vec<u8>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 pub struct DkgRound2Msg {
     pub source: u16,
     pub destination: u16,
     pub payload: Vec<u8>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 pub struct DkgRound3Msg {
     pub source: u16,
     pub payload: Vec<u8>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 pub struct DkgRound4Msg {
     pub source: u16,
     pub payload: Vec<u8>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-pub struct PkShareMsg {
+/// Asks the other parties to send their messages of `round` and later
+/// rounds again, sent by a party resuming the DKG after a restart.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct ResendMsg {
     pub source: u16,
-    pub data: Vec<u8>,
+    pub round: u16,
 }
 
 pub trait HasRecipient {
@@ -77,43 +154,441 @@ impl HasRecipient for KeygenMsg {
             KeygenMsg::DkgRound1(_)
             | KeygenMsg::DkgRound3(_)
             | KeygenMsg::DkgRound4(_)
-            | KeygenMsg::PkShareBroadcast(_) => MessageDestination::AllParties,
+            | KeygenMsg::Resend(_) => MessageDestination::AllParties,
             KeygenMsg::DkgRound2(msg) => MessageDestination::OneParty(msg.destination),
         }
     }
 }
 
-#[tracing::instrument(skip_all)]
+/// Names the type of a protocol message in metrics.
+pub trait MessageKind {
+    fn kind(&self) -> &'static str;
+}
+
+impl MessageKind for KeygenMsg {
+    fn kind(&self) -> &'static str {
+        match self {
+            KeygenMsg::DkgRound1(_) => "DkgRound1",
+            KeygenMsg::DkgRound2(_) => "DkgRound2",
+            KeygenMsg::DkgRound3(_) => "DkgRound3",
+            KeygenMsg::DkgRound4(_) => "DkgRound4",
+            KeygenMsg::Resend(_) => "Resend",
+        }
+    }
+}
+
+/// A keygen DKG's progress, saved after every round so an operator that
+/// restarts can resume it.
+///
+/// The party's DKG randomness is drawn from `seed` and its rounds are
+/// deterministic, so replaying the received messages recreates its state
+/// and the messages it sent before the restart.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DkgCheckpoint {
+    seed: [u8; 32],
+    /// Messages received in each completed round, by sender
+    rounds: Vec<BTreeMap<PartyIndex, KeygenMsg>>,
+    /// Parties left out after missing a round
+    excluded: BTreeSet<PartyIndex>,
+}
+
+impl DkgCheckpoint {
+    pub(crate) fn new() -> Self {
+        let mut seed = [0u8; 32];
+        OsRng.fill_bytes(&mut seed);
+        Self {
+            seed,
+            rounds: Vec::new(),
+            excluded: BTreeSet::new(),
+        }
+    }
+
+    /// Rounds completed before the checkpoint was saved.
+    pub fn completed_rounds(&self) -> usize {
+        self.rounds.len()
+    }
+}
+
+impl Drop for DkgCheckpoint {
+    fn drop(&mut self) {
+        self.seed.zeroize();
+    }
+}
+
+/// Persists a keygen's checkpoint after every round.
+pub type SaveCheckpoint = Box<dyn FnMut(&DkgCheckpoint) -> Result<(), KeygenError> + Send>;
+
+/// A group the DKG can generate keys in.
+trait DkgGroup: KeyGroup + GroupHasher + SumOfProducts + GroupEncoding + Default {}
+
+impl<G: KeyGroup + GroupHasher + SumOfProducts + GroupEncoding + Default> DkgGroup for G {}
+
+/// Generates a new key among `n` parties with threshold `t`.
+///
+/// The DKG is checkpointed through `save` before it starts and after every
+/// round. Passing a saved checkpoint as `resume` continues the DKG where it
+/// stopped: the completed rounds are replayed and the other parties are
+/// asked to send the messages of the current round again. Parties only
+/// answer while their own DKG runs, so the DKG can be resumed as long as
+/// they have not completed it or left this party out after missing a
+/// round's deadline.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip_all, fields(party = i, t, n))]
 pub async fn bls_keygen_protocol<M>(
     party: M,
     i: PartyIndex,
     t: u16,
     n: u16,
+    scheme: KeyScheme,
     call_id: u64,
+    round_timeout: Duration,
+    resume: Option<DkgCheckpoint>,
+    save: SaveCheckpoint,
 ) -> Result<BlsState, KeygenError>
 where
     M: Mpc<ProtocolMessage = KeygenMsg>,
 {
-    let MpcParty { delivery, .. } = party.into_party();
-    let (incomings, mut outgoings) = delivery.split();
+    let checkpointing = Checkpointing::new(resume, save)?;
+    match scheme {
+        KeyScheme::MinimalPublicKeySize => {
+            keygen::<M, G1Projective>(party, i, t, n, call_id, round_timeout, checkpointing).await
+        }
+        KeyScheme::MinimalSignatureSize => {
+            keygen::<M, G2Projective>(party, i, t, n, call_id, round_timeout, checkpointing).await
+        }
+    }
+}
 
+async fn keygen<M, G>(
+    party: M,
+    i: PartyIndex,
+    t: u16,
+    n: u16,
+    call_id: u64,
+    round_timeout: Duration,
+    checkpointing: Checkpointing,
+) -> Result<BlsState, KeygenError>
+where
+    M: Mpc<ProtocolMessage = KeygenMsg>,
+    G: DkgGroup,
+{
     // Create gennaro-dkg participant with 1-indexed sequential IDs
-    let t_nz = NonZeroUsize::new(t as usize).expect("T > 0");
-    let n_nz = NonZeroUsize::new(n as usize).expect("N > 0");
-    let parameters = Parameters::new(t_nz, n_nz, None, None, None);
+    let parameters = dkg_parameters::<G>(t, n);
+    let my_id = IdentifierPrimeField(participant_id(i));
+    let participant =
+        SecretParticipant::<G>::from_seed(my_id, &parameters, checkpointing.checkpoint.seed)
+            .map_err(|e| KeygenError::MpcError(e.to_string()))?;
+
+    // The DKG share and public key are the final key material
+    let (secret_key_bytes, group_pk, verification_shares) = run_dkg(
+        party,
+        i,
+        t,
+        n,
+        BTreeSet::new(),
+        participant,
+        round_timeout,
+        Some(checkpointing),
+        |share, pk, vs| Ok((share, pk, vs)),
+    )
+    .await?;
+
+    info!("[BLS-DKG] Keygen complete for party {i}");
+
+    Ok(BlsState {
+        secret_key_bytes: Some(secret_key_bytes),
+        uncompressed_pk: Some(group_pk.to_uncompressed()),
+        scheme: G::SCHEME,
+        verification_shares,
+        call_id,
+        t,
+        ..Default::default()
+    })
+}
 
-    let my_id = IdentifierPrimeField(Scalar::from((i + 1) as u64));
-    let mut participant = SecretParticipant::<G1Projective>::new(my_id, &parameters)
+/// Proactively refreshes a stored key share.
+///
+/// Runs the DKG on a zero secret and adds the resulting share to the current
+/// one, re-randomizing every share while keeping the group public key. The
+/// new verification shares must still interpolate to the stored key. Every
+/// holder of a share must take part: the refresh times out rather than
+/// leaving any out. Parties without a share are left out from the start.
+#[tracing::instrument(skip_all, fields(party = i, t = state.t, n))]
+pub async fn bls_refresh_protocol<M>(
+    party: M,
+    i: PartyIndex,
+    n: u16,
+    state: &BlsState,
+    round_timeout: Duration,
+) -> Result<BlsState, KeygenError>
+where
+    M: Mpc<ProtocolMessage = KeygenMsg>,
+{
+    match state.scheme {
+        KeyScheme::MinimalPublicKeySize => {
+            refresh::<M, G1Projective>(party, i, n, state, round_timeout).await
+        }
+        KeyScheme::MinimalSignatureSize => {
+            refresh::<M, G2Projective>(party, i, n, state, round_timeout).await
+        }
+    }
+}
+
+async fn refresh<M, G>(
+    party: M,
+    i: PartyIndex,
+    n: u16,
+    state: &BlsState,
+    round_timeout: Duration,
+) -> Result<BlsState, KeygenError>
+where
+    M: Mpc<ProtocolMessage = KeygenMsg>,
+    G: DkgGroup,
+{
+    let current_share = share_scalar(state)?;
+    let group_pk = group_public_key::<G>(state)?;
+    let current_verification_shares = state
+        .verification_shares
+        .iter()
+        .map(|(j, share)| point_from_bytes::<G>(share).map(|share| (*j, share)))
+        .collect::<Result<BTreeMap<_, G>, _>>()?;
+
+    let parameters = dkg_parameters::<G>(state.t, n);
+    let my_id = IdentifierPrimeField(participant_id(i));
+    let participant = RefreshParticipant::<G>::new(my_id, &parameters)
         .map_err(|e| KeygenError::MpcError(e.to_string()))?;
 
-    // Setup round-based router: 4 DKG rounds + 1 PK aggregation round
-    let mut rounds = RoundsRouter::builder();
-    let r1 = rounds.add_round(RoundInput::<DkgRound1Msg>::broadcast(i, n));
-    let r2 = rounds.add_round(RoundInput::<DkgRound2Msg>::p2p(i, n));
-    let r3 = rounds.add_round(RoundInput::<DkgRound3Msg>::broadcast(i, n));
-    let r4 = rounds.add_round(RoundInput::<DkgRound4Msg>::broadcast(i, n));
-    let r5 = rounds.add_round(RoundInput::<PkShareMsg>::broadcast(i, n));
-    let mut rounds = rounds.listen(incomings);
+    // Every holder of a share must take part, as an excluded one would keep
+    // a share that no longer matches the others. Parties the keygen left
+    // out hold none and are not waited for.
+    let holders = u16::try_from(current_verification_shares.len())
+        .map_err(|_| KeygenError::MpcError("Too many verification shares".into()))?;
+    let absent = (0..n)
+        .filter(|j| !current_verification_shares.contains_key(j))
+        .collect();
+    let (secret_key_bytes, _, verification_shares) = run_dkg(
+        party,
+        i,
+        holders,
+        n,
+        absent,
+        participant,
+        round_timeout,
+        None,
+        |refresh_share, refresh_pk, refresh_verification_shares| {
+            if !bool::from(refresh_pk.is_identity()) {
+                return Err(KeygenError::MpcError(
+                    "Refresh DKG produced a non-zero secret".into(),
+                ));
+            }
+            let verification_shares = refresh_verification_shares
+                .into_iter()
+                .map(|(j, refresh)| {
+                    let current = current_verification_shares.get(&j).ok_or_else(|| {
+                        KeygenError::MpcError(format!("No verification share for party {j}"))
+                    })?;
+                    Ok((j, *current + refresh))
+                })
+                .collect::<Result<_, KeygenError>>()?;
+            Ok((current_share + refresh_share, group_pk, verification_shares))
+        },
+    )
+    .await?;
+
+    info!("[BLS-DKG] Refresh complete for party {i}");
+
+    Ok(BlsState {
+        secret_key_bytes: Some(secret_key_bytes),
+        encrypted_secret_key: None,
+        verification_shares,
+        ..state.clone()
+    })
+}
+
+/// A holder of the key being reshared, seeding the new DKG with its share.
+pub struct ReshareDealer<'a> {
+    /// The holder's stored key share
+    pub state: &'a BlsState,
+    /// The holder's party index in the old committee
+    pub old_index: PartyIndex,
+    /// Old committee indices of all holders dealing in this reshare
+    pub dealers: Vec<PartyIndex>,
+}
+
+/// Reshares a key to a new committee of `n` parties with threshold `t`.
+///
+/// Holders of the old key seed the DKG with their Lagrange-weighted share,
+/// so the dealt secret is the old key. Parties without a share contribute a
+/// zero secret. Dealers check that the resulting group key is unchanged.
+/// All `n` parties must take part: the reshare times out rather than
+/// leaving any out, as the dealers' weights assume every dealer deals.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip_all, fields(party = i, t, n, dealer = dealer.is_some()))]
+pub async fn bls_reshare_protocol<M>(
+    party: M,
+    i: PartyIndex,
+    t: u16,
+    n: u16,
+    scheme: KeyScheme,
+    dealer: Option<ReshareDealer<'_>>,
+    call_id: u64,
+    round_timeout: Duration,
+) -> Result<BlsState, KeygenError>
+where
+    M: Mpc<ProtocolMessage = KeygenMsg>,
+{
+    if let Some(dealer) = &dealer
+        && dealer.state.scheme != scheme
+    {
+        return Err(KeygenError::MpcError(format!(
+            "Key uses the {:?} scheme, not {scheme:?}",
+            dealer.state.scheme
+        )));
+    }
+    match scheme {
+        KeyScheme::MinimalPublicKeySize => {
+            reshare::<M, G1Projective>(party, i, t, n, dealer, call_id, round_timeout).await
+        }
+        KeyScheme::MinimalSignatureSize => {
+            reshare::<M, G2Projective>(party, i, t, n, dealer, call_id, round_timeout).await
+        }
+    }
+}
+
+async fn reshare<M, G>(
+    party: M,
+    i: PartyIndex,
+    t: u16,
+    n: u16,
+    dealer: Option<ReshareDealer<'_>>,
+    call_id: u64,
+    round_timeout: Duration,
+) -> Result<BlsState, KeygenError>
+where
+    M: Mpc<ProtocolMessage = KeygenMsg>,
+    G: DkgGroup,
+{
+    let parameters = dkg_parameters::<G>(t, n);
+    let my_id = IdentifierPrimeField(participant_id(i));
+
+    let (secret_key_bytes, group_pk, verification_shares) = match dealer {
+        Some(dealer) => {
+            let expected_pk = group_public_key::<G>(dealer.state)?;
+            let old_share = DkgShare::with_identifier_and_value(
+                IdentifierPrimeField(participant_id(dealer.old_index)),
+                IdentifierPrimeField(share_scalar(dealer.state)?),
+            );
+            let dealer_ids = dealer
+                .dealers
+                .iter()
+                .map(|j| IdentifierPrimeField(participant_id(*j)))
+                .collect::<Vec<_>>();
+            let participant =
+                SecretParticipant::<G>::with_secret(my_id, &old_share, &parameters, &dealer_ids)
+                    .map_err(|e| KeygenError::MpcError(e.to_string()))?;
+
+            run_dkg(
+                party,
+                i,
+                n,
+                n,
+                BTreeSet::new(),
+                participant,
+                round_timeout,
+                None,
+                |share, pk, vs| {
+                    if pk != expected_pk {
+                        return Err(KeygenError::MpcError(
+                            "Reshared public key does not match the original key".into(),
+                        ));
+                    }
+                    Ok((share, pk, vs))
+                },
+            )
+            .await?
+        }
+        None => {
+            let participant = RefreshParticipant::<G>::new(my_id, &parameters)
+                .map_err(|e| KeygenError::MpcError(e.to_string()))?;
+
+            run_dkg(
+                party,
+                i,
+                n,
+                n,
+                BTreeSet::new(),
+                participant,
+                round_timeout,
+                None,
+                |share, pk, vs| {
+                    if bool::from(pk.is_identity()) {
+                        return Err(KeygenError::MpcError(
+                            "No key holders took part in the reshare".into(),
+                        ));
+                    }
+                    Ok((share, pk, vs))
+                },
+            )
+            .await?
+        }
+    };
+
+    info!("[BLS-DKG] Reshare complete for party {i}");
+
+    Ok(BlsState {
+        secret_key_bytes: Some(secret_key_bytes),
+        uncompressed_pk: Some(group_pk.to_uncompressed()),
+        scheme: G::SCHEME,
+        verification_shares,
+        call_id,
+        t,
+        ..Default::default()
+    })
+}
+
+/// Runs the four Gennaro DKG rounds with `participant` and derives every
+/// party's verification share from the dealers' Feldman commitments.
+///
+/// Parties that miss a round's `round_timeout` are left out of the rest of
+/// the protocol, so gennaro-dkg drops them from its valid participants, as
+/// are the `absent` parties from the start. The DKG fails with
+/// [`KeygenError::Timeout`] if fewer than `quorum` parties, this one
+/// included, remain. Parties may time out different peers, so
+/// disagreements on the remaining participants are not blamed on the sender.
+///
+/// `finalize` maps the DKG's secret share, public key and verification
+/// shares to this party's final share, the group key and the verification
+/// shares of the final shares, which must interpolate to the group key.
+///
+/// With `checkpointing`, the rounds already in its checkpoint are replayed
+/// and every completed round is saved.
+#[allow(clippy::too_many_arguments)]
+async fn run_dkg<M, I, G, F>(
+    party: M,
+    i: PartyIndex,
+    quorum: u16,
+    n: u16,
+    absent: BTreeSet<PartyIndex>,
+    mut participant: Participant<I, G>,
+    round_timeout: Duration,
+    checkpointing: Option<Checkpointing>,
+    finalize: F,
+) -> Result<(SecretShare, G, BTreeMap<u16, Vec<u8>>), KeygenError>
+where
+    M: Mpc<ProtocolMessage = KeygenMsg>,
+    I: ParticipantImpl<G> + Default,
+    G: DkgGroup,
+    F: FnOnce(Scalar, G, BTreeMap<PartyIndex, G>) -> DkgOutput<G>,
+{
+    let MpcParty { delivery, .. } = party.into_party();
+    let (incomings, outgoings) = delivery.split();
+
+    // 4 DKG rounds, each with its own deadline
+    let mut rounds =
+        DkgRounds::new(incomings, outgoings, i, quorum, n, round_timeout).without(absent);
+    if let Some(checkpointing) = checkpointing {
+        rounds = rounds.checkpointed(checkpointing);
+    }
 
     // --- DKG Round 1: Broadcast commitment hashes ---
     // NOTE: generator.iter() returns Box<dyn Iterator> which is !Send.
@@ -128,19 +603,18 @@ where
         .next()
         .ok_or_else(|| KeygenError::MpcError("No round 1 output".into()))?
         .data;
-    send_msg::<M>(
-        &mut outgoings,
-        KeygenMsg::DkgRound1(DkgRound1Msg { source: i, payload }),
-    )
-    .await?;
+    rounds
+        .send(KeygenMsg::DkgRound1(DkgRound1Msg { source: i, payload }))
+        .await?;
 
-    for (_, _, msg) in rounds
-        .complete(r1)
-        .await
-        .map_err(mpc_err)?
-        .into_iter_indexed()
+    for (sender, msg) in rounds
+        .complete::<DkgRound1Msg>(MessageType::Broadcast)
+        .await?
     {
-        participant.receive(&msg.payload).map_err(dkg_err)?;
+        check_source(sender, msg.source, 1)?;
+        participant
+            .receive(&msg.payload)
+            .map_err(|e| misbehavior(sender, 1, e))?;
     }
 
     // --- DKG Round 2: P2P shares ---
@@ -155,16 +629,14 @@ where
             destination: output.dst_ordinal as u16,
             payload: output.data,
         });
-        send_msg::<M>(&mut outgoings, msg).await?;
+        rounds.send(msg).await?;
     }
 
-    for (_, _, msg) in rounds
-        .complete(r2)
-        .await
-        .map_err(mpc_err)?
-        .into_iter_indexed()
-    {
-        participant.receive(&msg.payload).map_err(dkg_err)?;
+    for (sender, msg) in rounds.complete::<DkgRound2Msg>(MessageType::P2P).await? {
+        check_source(sender, msg.source, 2)?;
+        participant
+            .receive(&msg.payload)
+            .map_err(|e| misbehavior(sender, 2, e))?;
     }
 
     // --- DKG Round 3: Feldman commitments ---
@@ -178,19 +650,18 @@ where
         .next()
         .ok_or_else(|| KeygenError::MpcError("No round 3 output".into()))?
         .data;
-    send_msg::<M>(
-        &mut outgoings,
-        KeygenMsg::DkgRound3(DkgRound3Msg { source: i, payload }),
-    )
-    .await?;
+    rounds
+        .send(KeygenMsg::DkgRound3(DkgRound3Msg { source: i, payload }))
+        .await?;
 
-    for (_, _, msg) in rounds
-        .complete(r3)
-        .await
-        .map_err(mpc_err)?
-        .into_iter_indexed()
+    for (sender, msg) in rounds
+        .complete::<DkgRound3Msg>(MessageType::Broadcast)
+        .await?
     {
-        participant.receive(&msg.payload).map_err(dkg_err)?;
+        check_source(sender, msg.source, 3)?;
+        participant
+            .receive(&msg.payload)
+            .map_err(|e| receive_err(sender, 3, e))?;
     }
 
     // --- DKG Round 4: Transcript verification ---
@@ -204,19 +675,18 @@ where
         .next()
         .ok_or_else(|| KeygenError::MpcError("No round 4 output".into()))?
         .data;
-    send_msg::<M>(
-        &mut outgoings,
-        KeygenMsg::DkgRound4(DkgRound4Msg { source: i, payload }),
-    )
-    .await?;
+    rounds
+        .send(KeygenMsg::DkgRound4(DkgRound4Msg { source: i, payload }))
+        .await?;
 
-    for (_, _, msg) in rounds
-        .complete(r4)
-        .await
-        .map_err(mpc_err)?
-        .into_iter_indexed()
+    for (sender, msg) in rounds
+        .complete::<DkgRound4Msg>(MessageType::Broadcast)
+        .await?
     {
-        participant.receive(&msg.payload).map_err(dkg_err)?;
+        check_source(sender, msg.source, 4)?;
+        participant
+            .receive(&msg.payload)
+            .map_err(|e| receive_err(sender, 4, e))?;
     }
 
     // --- DKG Round 5: Internal computation (no network) ---
@@ -226,59 +696,534 @@ where
         "DKG should be complete after round 5"
     );
 
-    // Extract secret share scalar
+    // Extract the DKG's secret share and Feldman-derived public key
     let secret_share = participant
         .get_secret_share()
         .ok_or_else(|| KeygenError::MpcError("DKG incomplete: no secret share".into()))?;
-    let scalar: Scalar = *secret_share.value;
-    let secret_key_bytes = scalar.to_be_bytes().to_vec();
-
-    // --- Round 5 (network): Broadcast milagro pk shares for aggregation ---
-    info!("[BLS-DKG] Round 5: PK share broadcast + aggregation");
-    let milagro_sk = snowbridge_milagro_bls::SecretKey::from_bytes(&secret_key_bytes)
-        .map_err(|e| KeygenError::MpcError(format!("Failed to create milagro SK: {e:?}")))?;
-    let pk_share = snowbridge_milagro_bls::PublicKey::from_secret_key(&milagro_sk);
-    let my_pk_msg = PkShareMsg {
-        source: i,
-        data: pk_share.as_uncompressed_bytes().to_vec(),
-    };
-    send_msg::<M>(
-        &mut outgoings,
-        KeygenMsg::PkShareBroadcast(my_pk_msg.clone()),
-    )
-    .await?;
+    let dkg_pk = participant
+        .get_public_key()
+        .ok_or_else(|| KeygenError::MpcError("DKG incomplete: no public key".into()))?;
 
-    let pk_received = rounds.complete(r5).await.map_err(mpc_err)?;
-    let all_pk_msgs = pk_received.into_vec_including_me(my_pk_msg);
-    let all_pk_shares: Result<Vec<_>, _> = all_pk_msgs
+    // Every party that dealt in round 3 holds a share, committed to by the
+    // sum of the dealers' Feldman commitments evaluated at its identifier
+    let dkg_verification_shares = participant
+        .get_valid_participant_ids()
         .iter()
-        .map(|msg| snowbridge_milagro_bls::PublicKey::from_uncompressed_bytes(&msg.data))
-        .collect();
-    let all_pk_shares =
-        all_pk_shares.map_err(|e| KeygenError::MpcError(format!("Bad pk share: {e:?}")))?;
+        .map(|(ordinal, id)| {
+            let party = PartyIndex::try_from(*ordinal)
+                .map_err(|_| KeygenError::MpcError(format!("Invalid participant {ordinal}")))?;
+            let share = participant.get_verification_share(*id).ok_or_else(|| {
+                KeygenError::MpcError("DKG incomplete: no verification shares".into())
+            })?;
+            Ok((party, share))
+        })
+        .collect::<Result<BTreeMap<_, _>, KeygenError>>()?;
 
-    let pk_agg = snowbridge_milagro_bls::AggregatePublicKey::aggregate(
-        &all_pk_shares.iter().collect::<Vec<_>>(),
-    )
-    .map_err(|e| KeygenError::MpcError(format!("Failed to aggregate PKs: {e:?}")))?;
+    let (scalar, group_pk, verification_shares) =
+        finalize(*secret_share.value, dkg_pk, dkg_verification_shares)?;
+    let verification_shares = verification_shares
+        .into_iter()
+        .map(|(party, share)| (party, share.to_compressed()))
+        .collect::<BTreeMap<_, _>>();
 
-    let mut uncompressed_pk = [0u8; 97];
-    pk_agg.point.to_bytes(&mut uncompressed_pk, false);
+    // The verification shares must interpolate to the group public key and
+    // commit to this party's share
+    if verification_shares.get(&i) != Some(&(G::generator() * scalar).to_compressed()) {
+        return Err(KeygenError::MpcError(
+            "Verification share does not match this party's share".into(),
+        ));
+    }
+    if interpolate_verification_shares::<G>(&verification_shares)? != group_pk {
+        return Err(KeygenError::MpcError(
+            "Verification shares do not match the group public key".into(),
+        ));
+    }
 
-    info!("[BLS-DKG] Keygen complete for party {i}");
+    let mut scalar_bytes = scalar.to_be_bytes();
+    let secret_key_bytes = SecretShare::new(scalar_bytes.to_vec());
+    scalar_bytes.zeroize();
 
-    Ok(BlsState {
-        secret_key_bytes: Some(secret_key_bytes),
-        uncompressed_pk: Some(uncompressed_pk.to_vec()),
-        call_id,
-        t,
-    })
+    Ok((secret_key_bytes, group_pk, verification_shares))
+}
+
+/// A party's share, the group key and the parties' verification shares.
+type DkgOutput<G> = Result<(Scalar, G, BTreeMap<PartyIndex, G>), KeygenError>;
+
+/// Saves a DKG's progress after every round, resuming from a checkpoint
+/// saved before a restart.
+struct Checkpointing {
+    checkpoint: DkgCheckpoint,
+    save: SaveCheckpoint,
+    /// Whether the other parties still have to be asked to send the
+    /// messages this party missed while it was down
+    resumed: bool,
+}
+
+impl Checkpointing {
+    /// Resumes from `resume`, or starts a new checkpoint and saves it before
+    /// any message that depends on its seed is sent.
+    fn new(resume: Option<DkgCheckpoint>, mut save: SaveCheckpoint) -> Result<Self, KeygenError> {
+        let resumed = resume.is_some();
+        let checkpoint = match resume {
+            Some(checkpoint) => {
+                info!(
+                    "[BLS-DKG] Resuming after {} completed rounds",
+                    checkpoint.completed_rounds()
+                );
+                checkpoint
+            }
+            None => {
+                let checkpoint = DkgCheckpoint::new();
+                save(&checkpoint)?;
+                checkpoint
+            }
+        };
+        Ok(Self {
+            checkpoint,
+            save,
+            resumed,
+        })
+    }
+}
+
+/// Collects the DKG's incoming messages round by round, giving every round
+/// `round_timeout` to complete, and sends this party's messages.
+struct DkgRounds<S, O> {
+    incomings: S,
+    outgoings: O,
+    i: PartyIndex,
+    /// Fewest parties, this one included, that must complete every round
+    quorum: u16,
+    n: u16,
+    round_timeout: Duration,
+    /// When the current round started, for round latency metrics
+    round_started: Instant,
+    /// Messages that arrived before their round started, by round
+    early: BTreeMap<u16, Vec<Incoming<KeygenMsg>>>,
+    /// Parties that missed a deadline and are ignored from then on
+    excluded: BTreeSet<PartyIndex>,
+    /// Messages this party sent, to send again on request
+    sent: Vec<KeygenMsg>,
+    checkpointing: Option<Checkpointing>,
+}
+
+impl<S, E, O> DkgRounds<S, O>
+where
+    S: Stream<Item = Result<Incoming<KeygenMsg>, E>> + Unpin,
+    E: std::fmt::Display,
+    O: Sink<Outgoing<KeygenMsg>> + Unpin,
+    O::Error: std::fmt::Display,
+{
+    fn new(
+        incomings: S,
+        outgoings: O,
+        i: PartyIndex,
+        quorum: u16,
+        n: u16,
+        round_timeout: Duration,
+    ) -> Self {
+        Self {
+            incomings,
+            outgoings,
+            i,
+            quorum,
+            n,
+            round_timeout,
+            round_started: Instant::now(),
+            early: BTreeMap::new(),
+            excluded: BTreeSet::new(),
+            sent: Vec::new(),
+            checkpointing: None,
+        }
+    }
+
+    /// Leaves the `absent` parties, which do not take part, out from the
+    /// start.
+    fn without(mut self, absent: BTreeSet<PartyIndex>) -> Self {
+        self.excluded.extend(absent);
+        self
+    }
+
+    /// Replays the rounds in `checkpointing`'s checkpoint and saves every
+    /// round completed from then on.
+    fn checkpointed(mut self, checkpointing: Checkpointing) -> Self {
+        self.excluded
+            .extend(checkpointing.checkpoint.excluded.iter().copied());
+        self.checkpointing = Some(checkpointing);
+        self
+    }
+
+    /// Sends this party's message, keeping it to send again to parties
+    /// that resume after a restart.
+    async fn send(&mut self, msg: KeygenMsg) -> Result<(), KeygenError> {
+        // After a restart, the other parties are first asked for the
+        // messages of the rounds not in the checkpoint, before this party's
+        // messages let them move on
+        if let Some(checkpointing) = &mut self.checkpointing
+            && std::mem::take(&mut checkpointing.resumed)
+        {
+            let round = u16::try_from(checkpointing.checkpoint.rounds.len()).unwrap_or(u16::MAX);
+            let request = KeygenMsg::Resend(ResendMsg {
+                source: self.i,
+                round,
+            });
+            deliver(&mut self.outgoings, request).await?;
+        }
+        self.sent.push(msg.clone());
+        deliver(&mut self.outgoings, msg).await
+    }
+
+    /// Sends this party's messages of `round` and later rounds to `party`
+    /// again. Broadcasts go to every party, which drop the copies they
+    /// already have.
+    async fn resend(&mut self, party: PartyIndex, round: u16) -> Result<(), KeygenError> {
+        let messages = self
+            .sent
+            .iter()
+            .filter(|msg| msg.round() >= round)
+            .filter(|msg| match msg.recipient() {
+                MessageDestination::AllParties => true,
+                MessageDestination::OneParty(recipient) => recipient == party,
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        debug!(
+            "[BLS-DKG] Sending party {party} {} messages from round {} again",
+            messages.len(),
+            round + 1
+        );
+        for msg in messages {
+            deliver(&mut self.outgoings, msg).await?;
+        }
+        Ok(())
+    }
+
+    /// Waits for round `R`'s message from every remaining party, sent as
+    /// `msg_type`. Parties still missing at the deadline are excluded if a
+    /// quorum responded, otherwise the DKG times out. Rounds saved in the
+    /// checkpoint are replayed from it instead.
+    async fn complete<R>(
+        &mut self,
+        msg_type: MessageType,
+    ) -> Result<BTreeMap<PartyIndex, R>, KeygenError>
+    where
+        KeygenMsg: RoundMessage<R>,
+        R: Clone + PartialEq,
+    {
+        let round = <KeygenMsg as RoundMessage<R>>::ROUND;
+        if let Some(received) = self.replay(round) {
+            return received;
+        }
+
+        let deadline = Instant::now() + self.round_timeout;
+        let mut received = BTreeMap::new();
+
+        for incoming in self.early.remove(&round).unwrap_or_default() {
+            self.accept(&mut received, incoming, msg_type)?;
+        }
+        while received.len() + self.excluded.len() + 1 < usize::from(self.n) {
+            let incoming = match tokio::time::timeout_at(deadline, self.incomings.next()).await {
+                Ok(Some(incoming)) => incoming.map_err(mpc_err)?,
+                Ok(None) => {
+                    return Err(KeygenError::MpcError(
+                        "Incoming message stream ended".into(),
+                    ));
+                }
+                Err(_) => {
+                    self.exclude_missing(round, &received)?;
+                    break;
+                }
+            };
+            match &incoming.msg {
+                KeygenMsg::Resend(msg) => {
+                    let sender = incoming.sender;
+                    if sender != self.i && sender < self.n && !self.excluded.contains(&sender) {
+                        check_source(sender, msg.source, display_round::<R>())?;
+                        self.resend(sender, msg.round).await?;
+                    }
+                }
+                msg if msg.round() == round => self.accept(&mut received, incoming, msg_type)?,
+                msg if msg.round() > round => {
+                    self.early.entry(msg.round()).or_default().push(incoming)
+                }
+                // Late messages of a round that already completed
+                _ => {}
+            }
+        }
+
+        self.save(&received)?;
+        metrics().observe_round("dkg", round + 1, self.round_started.elapsed());
+        self.round_started = Instant::now();
+        Ok(received)
+    }
+
+    /// The messages of `round` saved in the checkpoint, if it has them.
+    fn replay<R>(&self, round: u16) -> Option<Result<BTreeMap<PartyIndex, R>, KeygenError>>
+    where
+        KeygenMsg: RoundMessage<R>,
+    {
+        let saved = self
+            .checkpointing
+            .as_ref()?
+            .checkpoint
+            .rounds
+            .get(usize::from(round))?;
+        Some(
+            saved
+                .iter()
+                .map(|(sender, msg)| {
+                    let msg = RoundMessage::from_protocol_message(msg.clone()).map_err(|_| {
+                        KeygenError::MpcError(format!(
+                            "Checkpoint holds a message of another round for round {}",
+                            round + 1
+                        ))
+                    })?;
+                    Ok((*sender, msg))
+                })
+                .collect(),
+        )
+    }
+
+    /// Adds the messages of a completed round to the checkpoint and saves it.
+    fn save<R>(&mut self, received: &BTreeMap<PartyIndex, R>) -> Result<(), KeygenError>
+    where
+        KeygenMsg: RoundMessage<R>,
+        R: Clone,
+    {
+        let Some(checkpointing) = &mut self.checkpointing else {
+            return Ok(());
+        };
+        let checkpoint = &mut checkpointing.checkpoint;
+        checkpoint.rounds.push(
+            received
+                .iter()
+                .map(|(sender, msg)| (*sender, RoundMessage::to_protocol_message(msg.clone())))
+                .collect(),
+        );
+        checkpoint.excluded = self.excluded.clone();
+        (checkpointing.save)(checkpoint)
+    }
+
+    /// Adds a message of the current round from a remaining party. Copies
+    /// of a message already received, sent again on another party's
+    /// request, are dropped.
+    fn accept<R>(
+        &self,
+        received: &mut BTreeMap<PartyIndex, R>,
+        incoming: Incoming<KeygenMsg>,
+        msg_type: MessageType,
+    ) -> Result<(), KeygenError>
+    where
+        KeygenMsg: RoundMessage<R>,
+        R: PartialEq,
+    {
+        let sender = incoming.sender;
+        if sender == self.i || sender >= self.n || self.excluded.contains(&sender) {
+            return Ok(());
+        }
+        let round = display_round::<R>();
+        if incoming.msg_type != msg_type {
+            return Err(misbehavior(
+                sender,
+                round,
+                format!(
+                    "sent a {:?} message, expected {msg_type:?}",
+                    incoming.msg_type
+                ),
+            ));
+        }
+        let msg = RoundMessage::from_protocol_message(incoming.msg)
+            .map_err(|_| misbehavior(sender, round, "sent a message of another round"))?;
+        match received.get(&sender) {
+            Some(first) if *first == msg => Ok(()),
+            Some(_) => Err(misbehavior(sender, round, "sent a round message twice")),
+            None => {
+                received.insert(sender, msg);
+                Ok(())
+            }
+        }
+    }
+
+    /// Excludes the parties missing from `received` once `round` timed out,
+    /// or fails if fewer than the quorum, this one included, responded.
+    fn exclude_missing<R>(
+        &mut self,
+        round: u16,
+        received: &BTreeMap<PartyIndex, R>,
+    ) -> Result<(), KeygenError> {
+        let missing_parties: Vec<PartyIndex> = (0..self.n)
+            .filter(|j| *j != self.i && !self.excluded.contains(j) && !received.contains_key(j))
+            .collect();
+        let round = u8::try_from(round + 1).unwrap_or(u8::MAX);
+        if received.len() + 1 < usize::from(self.quorum) {
+            return Err(KeygenError::Timeout {
+                round,
+                missing_parties,
+            });
+        }
+        warn!("[BLS-DKG] Round {round} timed out, excluding parties {missing_parties:?}");
+        self.excluded.extend(missing_parties);
+        Ok(())
+    }
+}
+
+/// Sends `msg` to its recipients.
+async fn deliver<O>(outgoings: &mut O, msg: KeygenMsg) -> Result<(), KeygenError>
+where
+    O: Sink<Outgoing<KeygenMsg>> + Unpin,
+    O::Error: std::fmt::Display,
+{
+    let recipient = msg.recipient();
+    outgoings
+        .send(Outgoing { recipient, msg })
+        .await
+        .map_err(|e| KeygenError::DeliveryError(e.to_string()))
+}
+
+/// Round number of `R` as used in errors, counting from 1.
+fn display_round<R>() -> u8
+where
+    KeygenMsg: RoundMessage<R>,
+{
+    u8::try_from(<KeygenMsg as RoundMessage<R>>::ROUND + 1).unwrap_or(u8::MAX)
+}
+
+fn dkg_parameters<G: DkgGroup>(t: u16, n: u16) -> Parameters<'static, G> {
+    let t_nz = NonZeroUsize::new(t as usize).expect("T > 0");
+    let n_nz = NonZeroUsize::new(n as usize).expect("N > 0");
+    Parameters::new(t_nz, n_nz, None, None, None)
+}
+
+/// The group public key stored in `state`.
+pub(crate) fn group_public_key<G: KeyGroup>(state: &BlsState) -> Result<G, KeygenError> {
+    if state.scheme != G::SCHEME {
+        return Err(KeygenError::MpcError(format!(
+            "Key uses the {:?} scheme, not {:?}",
+            state.scheme,
+            G::SCHEME
+        )));
+    }
+    let uncompressed_pk = state
+        .uncompressed_pk
+        .as_ref()
+        .ok_or_else(|| KeygenError::MpcError("Public key not found in state".into()))?;
+    G::from_uncompressed(uncompressed_pk)
+        .ok_or_else(|| KeygenError::MpcError("Invalid stored public key".into()))
+}
+
+/// The group public key in `state` in its compressed encoding, as signed
+/// by a proof of possession.
+pub(crate) fn compressed_public_key(state: &BlsState) -> Result<Vec<u8>, KeygenError> {
+    match state.scheme {
+        KeyScheme::MinimalPublicKeySize => {
+            group_public_key::<G1Projective>(state).map(|pk| KeyGroup::to_compressed(&pk))
+        }
+        KeyScheme::MinimalSignatureSize => {
+            group_public_key::<G2Projective>(state).map(|pk| KeyGroup::to_compressed(&pk))
+        }
+    }
+}
+
+/// The secret share scalar stored in `state`.
+pub(crate) fn share_scalar(state: &BlsState) -> Result<Scalar, KeygenError> {
+    let share = state
+        .secret_key_bytes
+        .as_ref()
+        .ok_or_else(|| KeygenError::MpcError("Secret key not found in state".into()))?;
+    let bytes: &[u8; 32] = share
+        .expose()
+        .try_into()
+        .map_err(|_| KeygenError::MpcError("Invalid secret key length".into()))?;
+    Option::from(Scalar::from_be_bytes(bytes))
+        .ok_or_else(|| KeygenError::MpcError("Invalid secret key".into()))
+}
+
+/// The gennaro-dkg identifier assigned to party `i` (1-indexed, sequential).
+pub(crate) fn participant_id(i: PartyIndex) -> Scalar {
+    Scalar::from(u64::from(i) + 1)
+}
+
+/// Lagrange coefficient at zero for `id` over the identifier set `ids`.
+pub(crate) fn lagrange_coefficient(id: Scalar, ids: &[Scalar]) -> Option<Scalar> {
+    let mut num = Scalar::ONE;
+    let mut den = Scalar::ONE;
+    for x_j in ids.iter().filter(|x_j| **x_j != id) {
+        num *= x_j;
+        den *= *x_j - id;
+    }
+    Option::from(den.invert()).map(|inv: Scalar| num * inv)
+}
+
+/// Interpolates compressed verification shares, keyed by party index, at zero.
+fn interpolate_verification_shares<G: KeyGroup>(
+    shares: &BTreeMap<u16, Vec<u8>>,
+) -> Result<G, KeygenError> {
+    let ids = shares
+        .keys()
+        .map(|i| participant_id(*i))
+        .collect::<Vec<_>>();
+    let mut point = G::identity();
+    for ((_, share), id) in shares.iter().zip(&ids) {
+        let coefficient = lagrange_coefficient(*id, &ids)
+            .ok_or_else(|| KeygenError::MpcError("Duplicate participant identifiers".into()))?;
+        point += point_from_bytes::<G>(share)? * coefficient;
+    }
+    Ok(point)
+}
+
+/// Decodes a compressed point of the public key group.
+pub(crate) fn point_from_bytes<G: KeyGroup>(bytes: &[u8]) -> Result<G, KeygenError> {
+    G::from_compressed(bytes).ok_or_else(|| KeygenError::MpcError("Invalid group point".into()))
 }
 
 fn dkg_err(e: gennaro_dkg::Error) -> KeygenError {
     KeygenError::MpcError(e.to_string())
 }
 
+/// Checks that a message's claimed source is the party that sent it.
+fn check_source(sender: PartyIndex, source: u16, round: u8) -> Result<(), KeygenError> {
+    if sender != source {
+        return Err(misbehavior(
+            sender,
+            round,
+            format!("claimed to be party {source}"),
+        ));
+    }
+    Ok(())
+}
+
+fn misbehavior<E: std::fmt::Display>(party: PartyIndex, round: u8, e: E) -> KeygenError {
+    KeygenError::Misbehavior {
+        party,
+        round,
+        reason: e.to_string(),
+    }
+}
+
+/// Maps a gennaro-dkg error receiving `sender`'s message in `round`.
+///
+/// Round 3 and 4 messages carry the sender's view of the remaining
+/// participants. A mismatch only shows that the two parties excluded
+/// different peers, so it fails the DKG without blaming the sender.
+fn receive_err(sender: PartyIndex, round: u8, e: gennaro_dkg::Error) -> KeygenError {
+    match &e {
+        gennaro_dkg::Error::RoundError(_, reason) if VIEW_MISMATCHES.contains(&reason.as_str()) => {
+            KeygenError::MpcError(format!(
+                "Party {sender} disagrees on the participants in round {round}: {reason}"
+            ))
+        }
+        _ => misbehavior(sender, round, e),
+    }
+}
+
+/// gennaro-dkg errors raised when a sender's participants differ from ours
+const VIEW_MISMATCHES: &[&str] = &[
+    "Valid participant ids do not match",
+    "Not a valid participant",
+    "Sender's transcript is incorrect",
+    "Sender has invalid public key",
+];
+
 fn mpc_err<E: std::fmt::Display>(e: E) -> KeygenError {
     KeygenError::MpcError(e.to_string())
 }
@@ -300,12 +1245,301 @@ where
     Ok(())
 }
 
-async fn send_msg<M>(
-    tx: &mut <<M as Mpc>::Delivery as Delivery<KeygenMsg>>::Send,
-    msg: KeygenMsg,
-) -> Result<(), KeygenError>
-where
-    M: Mpc<ProtocolMessage = KeygenMsg>,
-{
-    send_message::<M, KeygenMsg>(msg, tx).await
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blsful::inner_types::Group;
+    use chacha20poly1305::aead::OsRng;
+
+    /// Verification shares of a random `t`-of-`n` sharing, and the group key.
+    fn verification_shares(t: u16, n: u16) -> (BTreeMap<u16, Vec<u8>>, G1Projective) {
+        let coefficients = (0..t)
+            .map(|_| Scalar::random(&mut OsRng))
+            .collect::<Vec<_>>();
+        let shares = (0..n)
+            .map(|i| {
+                let x = participant_id(i);
+                let share = coefficients
+                    .iter()
+                    .rev()
+                    .fold(Scalar::ZERO, |acc, c| acc * x + c);
+                (
+                    i,
+                    KeyGroup::to_compressed(&(G1Projective::generator() * share)),
+                )
+            })
+            .collect();
+        (shares, G1Projective::generator() * coefficients[0])
+    }
+
+    #[test]
+    fn lagrange_coefficients_sum_to_one() {
+        let ids = [0, 2, 5, 6].map(participant_id);
+        let sum = ids
+            .iter()
+            .map(|id| lagrange_coefficient(*id, &ids).unwrap())
+            .fold(Scalar::ZERO, |acc, c| acc + c);
+        assert_eq!(sum, Scalar::ONE);
+    }
+
+    #[test]
+    fn any_t_verification_shares_interpolate_to_the_group_key() {
+        let (shares, group_pk) = verification_shares(3, 5);
+        assert_eq!(
+            interpolate_verification_shares::<G1Projective>(&shares).unwrap(),
+            group_pk
+        );
+        for subset in [[0, 1, 2], [1, 3, 4], [0, 2, 4]] {
+            let subset = shares
+                .iter()
+                .filter(|(i, _)| subset.contains(*i))
+                .map(|(i, share)| (*i, share.clone()))
+                .collect();
+            assert_eq!(
+                interpolate_verification_shares::<G1Projective>(&subset).unwrap(),
+                group_pk
+            );
+        }
+    }
+
+    type Outgoings = futures::channel::mpsc::UnboundedSender<Outgoing<KeygenMsg>>;
+    type Sent = futures::channel::mpsc::UnboundedReceiver<Outgoing<KeygenMsg>>;
+
+    fn round1(source: PartyIndex, payload: &[u8]) -> KeygenMsg {
+        KeygenMsg::DkgRound1(DkgRound1Msg {
+            source,
+            payload: payload.to_vec(),
+        })
+    }
+
+    /// Rounds of party 0 out of 4 receiving `messages` broadcast by their
+    /// senders, after which nothing arrives, and what party 0 sends.
+    fn dkg_rounds(
+        quorum: u16,
+        messages: Vec<KeygenMsg>,
+    ) -> (
+        DkgRounds<
+            impl Stream<Item = Result<Incoming<KeygenMsg>, std::convert::Infallible>> + Unpin,
+            Outgoings,
+        >,
+        Sent,
+    ) {
+        let incomings = messages
+            .into_iter()
+            .enumerate()
+            .map(|(id, msg)| {
+                let sender = match &msg {
+                    KeygenMsg::DkgRound1(msg) => msg.source,
+                    KeygenMsg::DkgRound2(msg) => msg.source,
+                    KeygenMsg::DkgRound3(msg) => msg.source,
+                    KeygenMsg::DkgRound4(msg) => msg.source,
+                    KeygenMsg::Resend(msg) => msg.source,
+                };
+                Ok(Incoming {
+                    id: id as u64,
+                    sender,
+                    msg_type: MessageType::Broadcast,
+                    msg,
+                })
+            })
+            .collect::<Vec<_>>();
+        let incomings = futures::stream::iter(incomings).chain(futures::stream::pending());
+        let (outgoings, sent) = futures::channel::mpsc::unbounded();
+        let rounds = DkgRounds::new(incomings, outgoings, 0, quorum, 4, Duration::from_secs(1));
+        (rounds, sent)
+    }
+
+    /// Checkpointing that records every save in `saved`.
+    fn checkpointing(
+        resume: Option<DkgCheckpoint>,
+        saved: &std::sync::Arc<std::sync::Mutex<Vec<DkgCheckpoint>>>,
+    ) -> Checkpointing {
+        let saved = saved.clone();
+        Checkpointing::new(
+            resume,
+            Box::new(move |checkpoint| {
+                saved.lock().unwrap().push(checkpoint.clone());
+                Ok(())
+            }),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn excludes_missing_parties_once_a_quorum_responded() {
+        let (mut rounds, _) = dkg_rounds(3, vec![round1(1, b""), round1(2, b"")]);
+        let received = rounds
+            .complete::<DkgRound1Msg>(MessageType::Broadcast)
+            .await
+            .unwrap();
+        assert_eq!(received.keys().collect::<Vec<_>>(), [&1, &2]);
+        assert_eq!(rounds.excluded, BTreeSet::from([3]));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn times_out_without_a_quorum() {
+        // A refresh needs every party
+        let (mut rounds, _) = dkg_rounds(4, vec![round1(1, b""), round1(2, b"")]);
+        let result = rounds
+            .complete::<DkgRound1Msg>(MessageType::Broadcast)
+            .await;
+        assert!(matches!(
+            result,
+            Err(KeygenError::Timeout { round: 1, missing_parties }) if missing_parties == [3]
+        ));
+        assert!(rounds.excluded.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn does_not_wait_for_absent_parties() {
+        // A refresh of a key whose keygen left party 3 out
+        let (rounds, _) = dkg_rounds(3, vec![round1(1, b"a"), round1(2, b"b")]);
+        let mut rounds = rounds.without(BTreeSet::from([3]));
+        let started = Instant::now();
+        let received = rounds
+            .complete::<DkgRound1Msg>(MessageType::Broadcast)
+            .await
+            .unwrap();
+        assert_eq!(received.keys().copied().collect::<Vec<_>>(), [1, 2]);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drops_copies_of_a_message_but_blames_different_ones() {
+        let (mut rounds, _) = dkg_rounds(
+            4,
+            vec![
+                round1(1, b"a"),
+                round1(1, b"a"),
+                round1(2, b"b"),
+                round1(3, b"c"),
+            ],
+        );
+        let received = rounds
+            .complete::<DkgRound1Msg>(MessageType::Broadcast)
+            .await
+            .unwrap();
+        assert_eq!(received.len(), 3);
+
+        let (mut rounds, _) = dkg_rounds(4, vec![round1(1, b"a"), round1(1, b"b")]);
+        assert!(matches!(
+            rounds
+                .complete::<DkgRound1Msg>(MessageType::Broadcast)
+                .await,
+            Err(KeygenError::Misbehavior {
+                party: 1,
+                round: 1,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sends_messages_again_on_request() {
+        let resend = KeygenMsg::Resend(ResendMsg {
+            source: 2,
+            round: 0,
+        });
+        let (mut rounds, mut sent) = dkg_rounds(
+            4,
+            vec![round1(1, b""), resend, round1(2, b""), round1(3, b"")],
+        );
+        rounds.send(round1(0, b"mine")).await.unwrap();
+        rounds
+            .complete::<DkgRound1Msg>(MessageType::Broadcast)
+            .await
+            .unwrap();
+        drop(rounds);
+
+        let sent = sent.by_ref().collect::<Vec<_>>().await;
+        assert_eq!(sent.len(), 2);
+        assert!(sent.iter().all(|outgoing| matches!(
+            &outgoing.msg,
+            KeygenMsg::DkgRound1(msg) if msg.payload == b"mine"
+        )));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn saves_every_round_and_replays_them_on_resume() {
+        let saved = std::sync::Arc::default();
+        let (rounds_before, _) = dkg_rounds(3, vec![round1(1, b"a"), round1(2, b"b")]);
+        let mut rounds_before = rounds_before.checkpointed(checkpointing(None, &saved));
+        rounds_before
+            .complete::<DkgRound1Msg>(MessageType::Broadcast)
+            .await
+            .unwrap();
+        let checkpoint = {
+            let saved = saved.lock().unwrap();
+            // Saved once before the DKG starts, to keep its seed
+            assert_eq!(saved.len(), 2);
+            assert_eq!(saved[0].completed_rounds(), 0);
+            assert_eq!(saved[1].excluded, BTreeSet::from([3]));
+            saved[1].clone()
+        };
+
+        // Nothing arrives after the restart, round 1 comes from the checkpoint
+        let (rounds_after, mut sent) = dkg_rounds(4, Vec::new());
+        let mut rounds_after = rounds_after.checkpointed(checkpointing(Some(checkpoint), &saved));
+        assert_eq!(rounds_after.excluded, BTreeSet::from([3]));
+        rounds_after.send(round1(0, b"mine")).await.unwrap();
+        let received = rounds_after
+            .complete::<DkgRound1Msg>(MessageType::Broadcast)
+            .await
+            .unwrap();
+        assert_eq!(received[&1].payload, b"a");
+        assert_eq!(received[&2].payload, b"b");
+        drop(rounds_after);
+
+        // The other parties are asked for what this party missed first
+        let sent = sent.by_ref().collect::<Vec<_>>().await;
+        assert!(matches!(
+            &sent[0].msg,
+            KeygenMsg::Resend(ResendMsg {
+                source: 0,
+                round: 1
+            })
+        ));
+        assert_eq!(sent.len(), 2);
+    }
+
+    #[test]
+    fn participants_from_the_same_seed_send_the_same_messages() {
+        let parameters = dkg_parameters::<G1Projective>(2, 3);
+        let id = IdentifierPrimeField(participant_id(0));
+        let round1 = |seed| {
+            let mut participant =
+                SecretParticipant::<G1Projective>::from_seed(id, &parameters, seed).unwrap();
+            let generator = participant.run().unwrap();
+            generator
+                .iter()
+                .map(|output| output.data)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(round1([1; 32]), round1([1; 32]));
+        assert_ne!(round1([1; 32]), round1([2; 32]));
+    }
+
+    #[test]
+    fn does_not_blame_disagreements_on_the_participants() {
+        let mismatch = gennaro_dkg::Error::RoundError(
+            gennaro_dkg::Round::Three,
+            "Valid participant ids do not match".into(),
+        );
+        assert!(matches!(
+            receive_err(2, 3, mismatch),
+            KeygenError::MpcError(_)
+        ));
+        let invalid = gennaro_dkg::Error::RoundError(
+            gennaro_dkg::Round::Three,
+            "The share does not verify with the given commitments".into(),
+        );
+        assert!(matches!(
+            receive_err(2, 3, invalid),
+            KeygenError::Misbehavior {
+                party: 2,
+                round: 3,
+                ..
+            }
+        ));
+    }
 }
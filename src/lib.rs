@@ -1,47 +1,296 @@
+pub mod ciphersuite;
 pub mod context;
 pub use context::BlsContext;
+pub mod error;
+pub use error::BlsError;
+pub mod get_key;
+pub use get_key::get_key;
 pub mod keygen;
 pub use keygen::keygen;
 pub(crate) mod keygen_state_machine;
+pub mod metrics;
+pub mod policy;
+pub mod refresh;
+pub use refresh::refresh;
+pub mod reshare;
+pub use reshare::reshare;
+pub mod retire;
+pub use retire::retire_key;
+pub mod secret;
+pub mod session;
+pub mod signers;
+pub use signers::set_signers;
 pub mod signing;
-pub use signing::sign;
+pub mod store;
+pub use signing::{prove_possession, sign, sign_batch};
 pub(crate) mod signing_state_machine;
+pub mod telemetry;
 
 use blueprint_sdk::Job;
 use blueprint_sdk::Router;
 use blueprint_sdk::alloy::sol;
 use blueprint_sdk::crypto::hashing::sha2_256;
 use blueprint_sdk::tangle::TangleLayer;
+use error::{JobFailure, ReportFailureLayer};
 
 /// Job IDs
 pub const JOB_KEYGEN: u8 = 0;
 pub const JOB_SIGN: u8 = 1;
+pub const JOB_REFRESH: u8 = 2;
+pub const JOB_RESHARE: u8 = 3;
+pub const JOB_SIGN_BATCH: u8 = 4;
+pub const JOB_PROVE_POSSESSION: u8 = 5;
+pub const JOB_GET_KEY: u8 = 6;
+pub const JOB_RETIRE_KEY: u8 = 7;
+pub const JOB_SET_SIGNERS: u8 = 8;
 
 const META_SALT: &str = "bls-protocol";
 
+// Every job result ends with the `code` and `message` of the job's
+// `BlsError`, 0 and empty when it succeeded
 sol! {
-    /// Keygen request: threshold value
+    /// Signing policy of a key, enforced by every operator before it
+    /// releases a signature share: a required message prefix (empty for
+    /// any), the longest message and most signatures per block allowed (0
+    /// for no limit), and slashing protection, which reads a slot (8 bytes,
+    /// big-endian) and domain (32 bytes) from the start of every message and
+    /// never signs two different messages for the same pair
+    struct PolicyConfig {
+        bytes message_prefix;
+        uint32 max_message_length;
+        uint32 max_signatures_per_block;
+        bool slashing_protection;
+    }
+
+    /// Keygen request: threshold value, key scheme (0 = G1 public keys
+    /// with G2 signatures, 1 = G2 public keys with G1 signatures), whether
+    /// to also produce a proof of possession of the key, the encoding of
+    /// points in the result (0 = compressed, 1 = uncompressed, 2 = EIP-2537)
+    /// and the key's signing policy
     struct KeygenRequest {
         uint16 t;
+        uint8 scheme;
+        bool proof_of_possession;
+        uint8 encoding;
+        PolicyConfig policy;
     }
 
-    /// Keygen result: the generated public key
+    /// Keygen result: the generated public key and, if requested, its proof
+    /// of possession, or, if keygen aborted because an operator misbehaved,
+    /// that operator with the failing round and reason as well
+    #[derive(Default)]
     struct KeygenResult {
         bytes public_key;
+        bytes proof_of_possession;
+        address culprit;
+        uint8 failed_round;
+        string reason;
+        uint16 code;
+        string message;
     }
 
-    /// Signing request: keygen call ID + message to sign
+    /// Signing request: keygen call ID + message to sign, the ciphersuite
+    /// (0 = NUL, 1 = AUG, 2 = POP) and an optional custom domain separation
+    /// tag replacing the ciphersuite's own, and the signature's encoding
     struct SignRequest {
         uint64 keygen_call_id;
         bytes message;
+        uint8 ciphersuite;
+        bytes dst;
+        uint8 encoding;
     }
 
-    /// Signing result: the signature
+    /// Signing result: the signature, and the operators whose signature
+    /// shares failed verification
+    #[derive(Default)]
     struct SignResult {
         bytes signature;
+        address[] offenders;
+        uint16 code;
+        string message;
+    }
+
+    /// Batch signing request: like a signing request, for many messages
+    /// signed in a single protocol run
+    struct SignBatchRequest {
+        uint64 keygen_call_id;
+        bytes[] messages;
+        uint8 ciphersuite;
+        bytes dst;
+        uint8 encoding;
+    }
+
+    /// Batch signing result: one signature per message, in request order,
+    /// and the operators whose signature shares failed verification
+    #[derive(Default)]
+    struct SignBatchResult {
+        bytes[] signatures;
+        address[] offenders;
+        uint16 code;
+        string message;
+    }
+
+    /// Proof of possession request: keygen call ID of the key to prove and
+    /// the result's point encoding
+    struct ProvePossessionRequest {
+        uint64 keygen_call_id;
+        uint8 encoding;
+    }
+
+    /// Proof of possession result: the public key, its proof of possession
+    /// under the POP ciphersuite, and the operators whose signature shares
+    /// failed verification
+    #[derive(Default)]
+    struct ProvePossessionResult {
+        bytes public_key;
+        bytes proof_of_possession;
+        address[] offenders;
+        uint16 code;
+        string message;
+    }
+
+    /// Key query request: keygen call ID of the key to look up and the
+    /// public key's encoding in the result
+    struct GetKeyRequest {
+        uint64 keygen_call_id;
+        uint8 encoding;
+    }
+
+    /// Key query result: the key's metadata as stored by the answering
+    /// operator, so the answers of all operators can be cross-checked, with
+    /// `retired_at_block` 0 while the key is in use
+    #[derive(Default)]
+    struct GetKeyResult {
+        bytes public_key;
+        uint16 t;
+        uint16 n;
+        address[] operators;
+        uint8 scheme;
+        uint64 created_at_block;
+        uint64 retired_at_block;
+        uint16 code;
+        string message;
+    }
+
+    /// Key retirement request: keygen call ID of the key to retire, which
+    /// only the key's owner may request
+    struct RetireKeyRequest {
+        uint64 keygen_call_id;
+    }
+
+    /// Key retirement result: confirms the key and the block it was
+    /// retired at
+    #[derive(Default)]
+    struct RetireKeyResult {
+        uint64 keygen_call_id;
+        uint64 retired_at_block;
+        uint16 code;
+        string message;
+    }
+
+    /// Delegated signers request: keygen call ID of the key and the full
+    /// list of addresses besides its owner allowed to sign with it, which
+    /// only the key's owner may set
+    struct SetSignersRequest {
+        uint64 keygen_call_id;
+        address[] signers;
+    }
+
+    /// Delegated signers result: the key and its signers as now stored
+    #[derive(Default)]
+    struct SetSignersResult {
+        uint64 keygen_call_id;
+        address[] signers;
+        uint16 code;
+        string message;
+    }
+
+    /// Refresh request: keygen call ID of the key whose shares to refresh
+    /// and the public key's encoding in the result
+    struct RefreshRequest {
+        uint64 keygen_call_id;
+        uint8 encoding;
+    }
+
+    /// Refresh result: the (unchanged) group public key
+    #[derive(Default)]
+    struct RefreshResult {
+        bytes public_key;
+        uint16 code;
+        string message;
+    }
+
+    /// Reshare request: keygen call ID of the key to move + new threshold,
+    /// the key's scheme and public key (in `encoding`) for operators that do
+    /// not hold it yet to check the reshared key against, the public key's
+    /// encoding in the result and the key's signing policy from now on
+    struct ReshareRequest {
+        uint64 keygen_call_id;
+        uint16 t;
+        uint8 scheme;
+        bytes public_key;
+        uint8 encoding;
+        PolicyConfig policy;
+    }
+
+    /// Reshare result: the (unchanged) group public key
+    #[derive(Default)]
+    struct ReshareResult {
+        bytes public_key;
+        uint16 code;
+        string message;
     }
 }
 
+impl JobFailure for KeygenResult {
+    fn failed(err: &BlsError) -> Self {
+        // Report the culprit on-chain so the service can slash or exclude it
+        let (culprit, failed_round, reason) = match err {
+            BlsError::Misbehavior {
+                operator,
+                round,
+                reason,
+            } => (*operator, *round, reason.clone()),
+            _ => Default::default(),
+        };
+        Self {
+            culprit,
+            failed_round,
+            reason,
+            code: err.code(),
+            message: err.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+macro_rules! impl_job_failure {
+    ($($result:ty),*) => {
+        $(
+            impl JobFailure for $result {
+                fn failed(err: &BlsError) -> Self {
+                    Self {
+                        code: err.code(),
+                        message: err.to_string(),
+                        ..Default::default()
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_job_failure!(
+    SignResult,
+    SignBatchResult,
+    ProvePossessionResult,
+    GetKeyResult,
+    RetireKeyResult,
+    SetSignersResult,
+    RefreshResult,
+    ReshareResult
+);
+
 /// Helper function to compute deterministic hashes for the BLS processes.
 /// Note: for signing, the "call_id" should be the call_id of the preceding
 /// keygen job
@@ -70,9 +319,62 @@ pub fn compute_deterministic_hashes(
     (meta_hash, deterministic_hash)
 }
 
-/// Router that maps job IDs to handlers.
+/// Router that maps job IDs to handlers, reporting their failures on chain.
 pub fn router() -> Router {
     Router::new()
-        .route(JOB_KEYGEN, keygen::keygen.layer(TangleLayer))
-        .route(JOB_SIGN, signing::sign.layer(TangleLayer))
+        .route(
+            JOB_KEYGEN,
+            keygen::keygen
+                .layer(ReportFailureLayer::<KeygenResult>::new())
+                .layer(TangleLayer),
+        )
+        .route(
+            JOB_SIGN,
+            signing::sign
+                .layer(ReportFailureLayer::<SignResult>::new())
+                .layer(TangleLayer),
+        )
+        .route(
+            JOB_REFRESH,
+            refresh::refresh
+                .layer(ReportFailureLayer::<RefreshResult>::new())
+                .layer(TangleLayer),
+        )
+        .route(
+            JOB_RESHARE,
+            reshare::reshare
+                .layer(ReportFailureLayer::<ReshareResult>::new())
+                .layer(TangleLayer),
+        )
+        .route(
+            JOB_SIGN_BATCH,
+            signing::sign_batch
+                .layer(ReportFailureLayer::<SignBatchResult>::new())
+                .layer(TangleLayer),
+        )
+        .route(
+            JOB_PROVE_POSSESSION,
+            signing::prove_possession
+                .layer(ReportFailureLayer::<ProvePossessionResult>::new())
+                .layer(TangleLayer),
+        )
+        .route(
+            JOB_GET_KEY,
+            get_key::get_key
+                .layer(ReportFailureLayer::<GetKeyResult>::new())
+                .layer(TangleLayer),
+        )
+        .route(
+            JOB_RETIRE_KEY,
+            retire::retire_key
+                .layer(ReportFailureLayer::<RetireKeyResult>::new())
+                .layer(TangleLayer),
+        )
+        .route(
+            JOB_SET_SIGNERS,
+            signers::set_signers
+                .layer(ReportFailureLayer::<SetSignersResult>::new())
+                .layer(TangleLayer),
+        )
+        .layer(metrics::JobMetricsLayer)
 }
@@ -1,4 +1,5 @@
-use bls_blueprint::context::BlsContext;
+use bls_blueprint::context::{BlsContext, bls_ctx};
+use bls_blueprint::keygen::{resumed_keygens, skip_resumed_keygens};
 use bls_blueprint::router;
 use blueprint_sdk::contexts::tangle::TangleClientContext;
 use blueprint_sdk::info;
@@ -6,6 +7,7 @@ use blueprint_sdk::runner::BlueprintRunner;
 use blueprint_sdk::runner::config::BlueprintEnvironment;
 use blueprint_sdk::runner::tangle::config::TangleConfig;
 use blueprint_sdk::tangle::{TangleConsumer, TangleProducer};
+use std::collections::HashSet;
 
 #[tokio::main]
 #[allow(clippy::result_large_err)]
@@ -19,6 +21,11 @@ async fn main() -> Result<(), blueprint_sdk::Error> {
         .await
         .map_err(blueprint_sdk::Error::Other)?;
 
+    // Serve Prometheus metrics if BLS_METRICS_ADDR is set
+    bls_blueprint::metrics::serve_from_env()
+        .await
+        .map_err(blueprint_sdk::Error::Other)?;
+
     let tangle_client = env
         .tangle_client()
         .await
@@ -33,13 +40,35 @@ async fn main() -> Result<(), blueprint_sdk::Error> {
 
     info!("Starting BLS blueprint for service {service_id}");
 
-    let tangle_producer = TangleProducer::new(tangle_client.clone(), service_id);
+    // Keygen jobs interrupted by a restart are picked up again from their
+    // blocks, so their DKGs resume from their checkpoints; no other calls
+    // before the producer's normal start are replayed
+    let interrupted = bls_ctx()
+        .checkpoints
+        .interrupted_jobs()
+        .map_err(|e| blueprint_sdk::Error::Other(e.to_string()))?;
+    let call_ids: HashSet<u64> = interrupted.keys().copied().collect();
+    let tangle_producer = skip_resumed_keygens(
+        TangleProducer::new(tangle_client.clone(), service_id),
+        call_ids.clone(),
+    );
+    let resume_producer = interrupted.values().min().map(|&block| {
+        info!("Resuming interrupted keygen jobs {call_ids:?} from block {block}");
+        resumed_keygens(
+            TangleProducer::from_block(tangle_client.clone(), service_id, block),
+            call_ids,
+        )
+    });
     let tangle_consumer = TangleConsumer::new(tangle_client);
     let tangle_config = TangleConfig::default();
 
-    BlueprintRunner::builder(tangle_config, env)
+    let mut runner = BlueprintRunner::builder(tangle_config, env)
         .router(router())
-        .producer(tangle_producer)
+        .producer(tangle_producer);
+    if let Some(resume_producer) = resume_producer {
+        runner = runner.producer(resume_producer);
+    }
+    runner
         .consumer(tangle_consumer)
         .with_shutdown_handler(async {
             info!("Shutting down BLS blueprint");
@@ -53,7 +82,18 @@ async fn main() -> Result<(), blueprint_sdk::Error> {
 fn setup_log() {
     use tracing_subscriber::prelude::*;
     use tracing_subscriber::{EnvFilter, fmt};
+
+    // Export spans over OTLP if OTEL_EXPORTER_OTLP_ENDPOINT is set
+    #[cfg(feature = "otel")]
+    let otel = bls_blueprint::telemetry::otel_layer().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        None
+    });
+    #[cfg(not(feature = "otel"))]
+    let otel = None::<tracing_subscriber::layer::Identity>;
+
     let _ = tracing_subscriber::registry()
+        .with(otel)
         .with(fmt::layer())
         .with(EnvFilter::from_default_env())
         .try_init();
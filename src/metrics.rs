@@ -0,0 +1,213 @@
+use crate::error::ERROR_CODE_METADATA_KEY;
+use axum::routing::get;
+use blueprint_sdk::core::error::BoxError;
+use blueprint_sdk::core::metadata::MetadataValue;
+use blueprint_sdk::{JobCall, JobResult};
+use prometheus::{
+    HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::LazyLock;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower::{Layer, Service};
+use tracing::{error, info};
+
+/// Environment variable holding the address to serve Prometheus metrics on,
+/// e.g. `0.0.0.0:9615`. Metrics are not served when it is unset.
+pub const METRICS_ADDR_ENV: &str = "BLS_METRICS_ADDR";
+
+/// Round latency buckets in seconds, up to the default DKG round timeout
+const ROUND_DURATION_BUCKETS: &[f64] = &[
+    0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0,
+];
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Get the global metrics.
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+/// Prometheus metrics of the BLS protocols.
+pub struct Metrics {
+    registry: Registry,
+    /// Jobs handled, by job ID, outcome (`ok` or `error`) and error code
+    pub jobs: IntCounterVec,
+    /// Time from the start of a protocol round to receiving all of its
+    /// messages, by protocol (`dkg` or `signing`) and round
+    pub round_duration: HistogramVec,
+    /// Serialized protocol message bytes, by direction (`sent` or
+    /// `received`) and message type
+    pub message_bytes: IntCounterVec,
+    /// Committee members connected when a protocol last started
+    pub connected_peers: IntGauge,
+    /// Size of the committee a protocol last started with
+    pub committee_size: IntGauge,
+    /// Keys in the local store, by status (`active` or `retired`)
+    pub stored_keys: IntGaugeVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("bls".into()), None).expect("valid registry");
+        let jobs = IntCounterVec::new(
+            Opts::new("jobs_total", "Jobs handled by outcome"),
+            &["job", "outcome", "code"],
+        )
+        .expect("valid metric");
+        let round_duration = HistogramVec::new(
+            HistogramOpts::new("round_duration_seconds", "Protocol round latency")
+                .buckets(ROUND_DURATION_BUCKETS.to_vec()),
+            &["protocol", "round"],
+        )
+        .expect("valid metric");
+        let message_bytes = IntCounterVec::new(
+            Opts::new("message_bytes_total", "Protocol message bytes"),
+            &["direction", "message"],
+        )
+        .expect("valid metric");
+        let connected_peers =
+            IntGauge::new("connected_peers", "Connected committee members").expect("valid metric");
+        let committee_size =
+            IntGauge::new("committee_size", "Committee size").expect("valid metric");
+        let stored_keys = IntGaugeVec::new(
+            Opts::new("stored_keys", "Keys in the local store"),
+            &["status"],
+        )
+        .expect("valid metric");
+
+        for collector in [
+            Box::new(jobs.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(round_duration.clone()),
+            Box::new(message_bytes.clone()),
+            Box::new(connected_peers.clone()),
+            Box::new(committee_size.clone()),
+            Box::new(stored_keys.clone()),
+        ] {
+            registry.register(collector).expect("unique metric");
+        }
+
+        Self {
+            registry,
+            jobs,
+            round_duration,
+            message_bytes,
+            connected_peers,
+            committee_size,
+            stored_keys,
+        }
+    }
+
+    /// Records that `round` of `protocol` completed after `elapsed`.
+    pub fn observe_round(&self, protocol: &str, round: u16, elapsed: Duration) {
+        self.round_duration
+            .with_label_values(&[protocol, &round.to_string()])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Counts `bytes` of a `message` sent or received.
+    pub fn count_message(&self, direction: &str, message: &str, bytes: usize) {
+        self.message_bytes
+            .with_label_values(&[direction, message])
+            .inc_by(bytes as u64);
+    }
+
+    /// Encodes all metrics in the Prometheus text format.
+    pub fn encode(&self) -> Result<String, String> {
+        TextEncoder::new()
+            .encode_to_string(&self.registry.gather())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Serves the metrics on `GET /metrics` at the address in
+/// [`METRICS_ADDR_ENV`], if it is set.
+pub async fn serve_from_env() -> Result<(), String> {
+    let Ok(addr) = std::env::var(METRICS_ADDR_ENV) else {
+        return Ok(());
+    };
+    let addr: SocketAddr = addr
+        .parse()
+        .map_err(|e| format!("{METRICS_ADDR_ENV} is not a socket address: {e}"))?;
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .map_err(|e| format!("Failed to bind metrics server to {addr}: {e}"))?;
+
+    let app = axum::Router::new().route(
+        "/metrics",
+        get(|| async { metrics().encode().unwrap_or_else(|e| e.to_string()) }),
+    );
+
+    info!("Serving metrics on http://{addr}/metrics");
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            error!("Metrics server stopped: {e}");
+        }
+    });
+    Ok(())
+}
+
+/// Counts the outcome of every job routed through it in [`Metrics::jobs`],
+/// labelling failures with the [`BlsError`](crate::BlsError) code that
+/// [`ReportFailureLayer`](crate::error::ReportFailureLayer) put in their
+/// results.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JobMetricsLayer;
+
+impl<S> Layer<S> for JobMetricsLayer {
+    type Service = JobMetrics<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        JobMetrics { inner }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct JobMetrics<S> {
+    inner: S,
+}
+
+impl<S> Service<JobCall> for JobMetrics<S>
+where
+    S: Service<JobCall, Response = Option<JobResult>, Error = BoxError>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, call: JobCall) -> Self::Future {
+        let job = u64::from(call.job_id()).to_string();
+        let future = self.inner.call(call);
+        Box::pin(async move {
+            let result = future.await;
+            let (outcome, code) = match &result {
+                Ok(Some(JobResult::Ok { head, .. })) => {
+                    match head.metadata.get(ERROR_CODE_METADATA_KEY) {
+                        Some(code) => ("error", error_code(code)),
+                        None => ("ok", String::new()),
+                    }
+                }
+                Ok(Some(JobResult::Err(_))) | Err(_) => ("error", "unknown".into()),
+                Ok(None) => ("ok", String::new()),
+            };
+            metrics()
+                .jobs
+                .with_label_values(&[job.as_str(), outcome, code.as_str()])
+                .inc();
+            result
+        })
+    }
+}
+
+/// The error code in a result's metadata.
+fn error_code(code: &MetadataValue) -> String {
+    u16::try_from(code).map_or_else(|_| "unknown".into(), |code| code.to_string())
+}
@@ -0,0 +1,339 @@
+use crate::PolicyConfig;
+use crate::keygen_state_machine::BlsState;
+use crate::signing::SigningError;
+use blueprint_sdk::alloy::primitives::Address;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Bytes a slashing-protected message starts with: slot (8 bytes,
+/// big-endian) || domain (32 bytes)
+pub const SLOT_DOMAIN_LEN: usize = 8 + 32;
+
+/// Slots below the highest signed one that slashing protection remembers.
+/// Messages for older slots are refused, as a different message may have
+/// been signed for them.
+pub const SLOT_HISTORY: u64 = 8192;
+
+/// Rules a key's messages must satisfy before an operator releases a
+/// signature share for them, configured at keygen.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SigningPolicy {
+    /// Prefix every message must start with, empty for any
+    #[serde(default)]
+    pub message_prefix: Vec<u8>,
+    /// Longest message allowed, 0 for no limit
+    #[serde(default)]
+    pub max_message_length: u32,
+    /// Most signatures per block, 0 for no limit
+    #[serde(default)]
+    pub max_signatures_per_block: u32,
+    /// Refuses to sign two different messages for the same slot and domain,
+    /// read from the first [`SLOT_DOMAIN_LEN`] bytes of every message
+    #[serde(default)]
+    pub slashing_protection: bool,
+}
+
+impl From<PolicyConfig> for SigningPolicy {
+    fn from(config: PolicyConfig) -> Self {
+        Self {
+            message_prefix: config.message_prefix.to_vec(),
+            max_message_length: config.max_message_length,
+            max_signatures_per_block: config.max_signatures_per_block,
+            slashing_protection: config.slashing_protection,
+        }
+    }
+}
+
+/// What an operator has signed with a key, as far as its policy needs to
+/// remember.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PolicyRecord {
+    /// Block of the latest signing request
+    #[serde(default)]
+    pub block: u64,
+    /// Signatures released in that block
+    #[serde(default)]
+    pub signatures_in_block: u32,
+    /// SHA-256 of the message signed per slot and domain, when slashing
+    /// protection is on
+    #[serde(default)]
+    pub signed_slots: BTreeMap<u64, Vec<SlotSignature>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlotSignature {
+    pub domain: [u8; 32],
+    pub message_hash: [u8; 32],
+}
+
+/// A signing request as seen by policy checks.
+pub struct PolicyRequest<'a> {
+    pub keygen_call_id: u64,
+    pub caller: Address,
+    pub block_number: u64,
+    pub messages: &'a [Vec<u8>],
+}
+
+/// An operator-wide check run on every signing request in addition to the
+/// key's own policy. Refusals should use [`SigningError::PolicyRefused`].
+pub trait PolicyHook: Send + Sync {
+    fn check(&self, state: &BlsState, request: &PolicyRequest<'_>) -> Result<(), SigningError>;
+}
+
+/// Runs `hooks` and the key's policy over `request` and, if all of them
+/// allow it, records it in the key's policy record.
+pub fn enforce(
+    state: &mut BlsState,
+    hooks: &[Arc<dyn PolicyHook>],
+    request: &PolicyRequest<'_>,
+) -> Result<(), SigningError> {
+    for hook in hooks {
+        hook.check(state, request)?;
+    }
+    let mut record = state.policy_record.clone();
+    state.policy.check(&mut record, request)?;
+    state.policy_record = record;
+    Ok(())
+}
+
+impl SigningPolicy {
+    /// Checks `request` against the policy, recording it in `record`.
+    fn check(
+        &self,
+        record: &mut PolicyRecord,
+        request: &PolicyRequest<'_>,
+    ) -> Result<(), SigningError> {
+        for (index, message) in request.messages.iter().enumerate() {
+            if !message.starts_with(&self.message_prefix) {
+                return Err(SigningError::MessagePrefixMismatch { index });
+            }
+            if self.max_message_length != 0 && message.len() > self.max_message_length as usize {
+                return Err(SigningError::MessageTooLong {
+                    index,
+                    len: message.len(),
+                    max: self.max_message_length,
+                });
+            }
+        }
+
+        if self.max_signatures_per_block != 0 {
+            if record.block != request.block_number {
+                record.block = request.block_number;
+                record.signatures_in_block = 0;
+            }
+            let signatures = u32::try_from(request.messages.len())
+                .ok()
+                .and_then(|n| record.signatures_in_block.checked_add(n))
+                .filter(|n| *n <= self.max_signatures_per_block)
+                .ok_or(SigningError::RateLimited {
+                    block: request.block_number,
+                    max: self.max_signatures_per_block,
+                })?;
+            record.signatures_in_block = signatures;
+        }
+
+        if self.slashing_protection {
+            for (index, message) in request.messages.iter().enumerate() {
+                record_slot(record, index, message)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Records `message` for its slot and domain, refusing it if a different
+/// message was signed for them before.
+fn record_slot(
+    record: &mut PolicyRecord,
+    index: usize,
+    message: &[u8],
+) -> Result<(), SigningError> {
+    if message.len() < SLOT_DOMAIN_LEN {
+        return Err(SigningError::MissingSlotDomain { index });
+    }
+    let (slot, rest) = message.split_at(8);
+    let slot = u64::from_be_bytes(slot.try_into().expect("8 bytes"));
+    let domain: [u8; 32] = rest[..32].try_into().expect("32 bytes");
+    let message_hash: [u8; 32] = Sha256::digest(message).into();
+
+    // Older slots have been pruned, or are about to be
+    let oldest = record
+        .signed_slots
+        .last_key_value()
+        .map_or(0, |(highest, _)| {
+            highest.max(&slot).saturating_sub(SLOT_HISTORY)
+        });
+    if slot < oldest {
+        return Err(SigningError::SlotTooOld {
+            index,
+            slot,
+            oldest,
+        });
+    }
+    record.signed_slots = record.signed_slots.split_off(&oldest);
+
+    let signed = record.signed_slots.entry(slot).or_default();
+    match signed.iter().find(|signed| signed.domain == domain) {
+        Some(signed) if signed.message_hash != message_hash => {
+            Err(SigningError::SlashableMessage {
+                index,
+                slot,
+                domain: hex::encode(domain),
+            })
+        }
+        Some(_) => Ok(()),
+        None => {
+            signed.push(SlotSignature {
+                domain,
+                message_hash,
+            });
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(block_number: u64, messages: &[Vec<u8>]) -> PolicyRequest<'_> {
+        PolicyRequest {
+            keygen_call_id: 1,
+            caller: Address::ZERO,
+            block_number,
+            messages,
+        }
+    }
+
+    fn state(policy: SigningPolicy) -> BlsState {
+        BlsState {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    /// A slashing-protected message for `slot` in a domain of `domain` bytes.
+    fn slot_message(slot: u64, domain: u8, body: &[u8]) -> Vec<u8> {
+        let mut message = slot.to_be_bytes().to_vec();
+        message.extend([domain; 32]);
+        message.extend(body);
+        message
+    }
+
+    #[test]
+    fn refuses_messages_without_the_prefix() {
+        let mut state = state(SigningPolicy {
+            message_prefix: b"eth".to_vec(),
+            ..Default::default()
+        });
+        assert!(enforce(&mut state, &[], &request(1, &[b"eth-1".to_vec()])).is_ok());
+        assert!(matches!(
+            enforce(
+                &mut state,
+                &[],
+                &request(1, &[b"eth-2".to_vec(), b"btc".to_vec()])
+            ),
+            Err(SigningError::MessagePrefixMismatch { index: 1 })
+        ));
+    }
+
+    #[test]
+    fn refuses_messages_over_the_length_limit() {
+        let mut state = state(SigningPolicy {
+            max_message_length: 4,
+            ..Default::default()
+        });
+        assert!(enforce(&mut state, &[], &request(1, &[vec![0; 4]])).is_ok());
+        assert!(matches!(
+            enforce(&mut state, &[], &request(1, &[vec![0; 5]])),
+            Err(SigningError::MessageTooLong {
+                index: 0,
+                len: 5,
+                max: 4
+            })
+        ));
+    }
+
+    #[test]
+    fn limits_signatures_per_block() {
+        let mut state = state(SigningPolicy {
+            max_signatures_per_block: 3,
+            ..Default::default()
+        });
+        assert!(enforce(&mut state, &[], &request(5, &[vec![1], vec![2]])).is_ok());
+        assert!(matches!(
+            enforce(&mut state, &[], &request(5, &[vec![3], vec![4]])),
+            Err(SigningError::RateLimited { block: 5, max: 3 })
+        ));
+        // The refused request is not counted
+        assert!(enforce(&mut state, &[], &request(5, &[vec![3]])).is_ok());
+        assert!(enforce(&mut state, &[], &request(5, &[vec![4]])).is_err());
+        // The count starts over in a new block
+        assert!(enforce(&mut state, &[], &request(6, &[vec![1], vec![2], vec![3]])).is_ok());
+    }
+
+    #[test]
+    fn refuses_a_different_message_for_a_signed_slot() {
+        let mut state = state(SigningPolicy {
+            slashing_protection: true,
+            ..Default::default()
+        });
+        let signed = slot_message(7, 1, b"a");
+        assert!(enforce(&mut state, &[], &request(1, std::slice::from_ref(&signed))).is_ok());
+        // Signing the same message again, or in another domain, is allowed
+        assert!(enforce(&mut state, &[], &request(2, &[signed])).is_ok());
+        assert!(enforce(&mut state, &[], &request(2, &[slot_message(7, 2, b"b")])).is_ok());
+        assert!(matches!(
+            enforce(&mut state, &[], &request(3, &[slot_message(7, 1, b"b")])),
+            Err(SigningError::SlashableMessage {
+                index: 0,
+                slot: 7,
+                ..
+            })
+        ));
+        assert!(matches!(
+            enforce(
+                &mut state,
+                &[],
+                &request(3, &[vec![0; SLOT_DOMAIN_LEN - 1]])
+            ),
+            Err(SigningError::MissingSlotDomain { index: 0 })
+        ));
+        // Nothing in a refused batch is recorded
+        assert!(
+            enforce(
+                &mut state,
+                &[],
+                &request(4, &[slot_message(9, 1, b"a"), slot_message(9, 1, b"b")])
+            )
+            .is_err()
+        );
+        assert!(enforce(&mut state, &[], &request(4, &[slot_message(9, 1, b"b")])).is_ok());
+    }
+
+    #[test]
+    fn prunes_old_slots_and_refuses_them() {
+        let mut state = state(SigningPolicy {
+            slashing_protection: true,
+            ..Default::default()
+        });
+        for slot in [1, 2, SLOT_HISTORY + 2] {
+            assert!(enforce(&mut state, &[], &request(1, &[slot_message(slot, 1, b"a")])).is_ok());
+        }
+        assert_eq!(
+            state.policy_record.signed_slots.keys().collect::<Vec<_>>(),
+            [&2, &(SLOT_HISTORY + 2)]
+        );
+        assert!(matches!(
+            enforce(&mut state, &[], &request(1, &[slot_message(1, 1, b"a")])),
+            Err(SigningError::SlotTooOld {
+                index: 0,
+                slot: 1,
+                oldest: 2
+            })
+        ));
+    }
+}
@@ -0,0 +1,119 @@
+use crate::RefreshRequest;
+use crate::RefreshResult;
+use crate::ciphersuite::PointEncoding;
+use crate::context::{Committee, bls_ctx};
+use crate::error::BlsError;
+use crate::keygen_state_machine::KeygenMsg;
+use crate::session::SessionNetwork;
+use blueprint_sdk::alloy::primitives::Address;
+use blueprint_sdk::info;
+use blueprint_sdk::tangle::extract::{CallId, Caller, ServiceId, TangleArg, TangleResult};
+use tracing::Instrument;
+
+const REFRESH_SALT: &str = "bls-refresh";
+
+/// Proactively refreshes the key shares of a previously generated key.
+///
+/// Extracts keygen_call_id from the on-chain request and runs a DKG on a zero
+/// secret among the key's committee. Each operator adds the result to its
+/// stored share, so shares are re-randomized while the group public key,
+/// which is returned, stays the same. Every operator of the committee
+/// holding a share has to take part, while those the keygen left out are
+/// not waited for. Only the key's owner may refresh it, and only one
+/// refresh or reshare of a key runs at a time.
+pub async fn refresh(
+    ServiceId(service_id): ServiceId,
+    CallId(call_id): CallId,
+    Caller(caller): Caller,
+    TangleArg(request): TangleArg<RefreshRequest>,
+) -> Result<TangleResult<RefreshResult>, BlsError> {
+    let ctx = bls_ctx();
+    let keygen_call_id = request.keygen_call_id;
+    let encoding = PointEncoding::try_from(request.encoding)?;
+    let caller = Address::from(caller);
+
+    // Held until the refreshed share is stored
+    let _key_guard = ctx.lock_key(keygen_call_id)?;
+
+    // Looked up by call ID, as the committee may have changed since keygen
+    let (store_key, state) = ctx
+        .store
+        .find_by_call_id(keygen_call_id)?
+        .ok_or(BlsError::KeyNotFound { keygen_call_id })?;
+    state.check_owner(caller)?;
+    if let Some(block) = state.retired_at_block {
+        return Err(BlsError::KeyRetired {
+            keygen_call_id,
+            block,
+        });
+    }
+
+    let t = state.t;
+
+    let committee = Committee::new(state.operators.clone(), ctx.operator_address)?;
+    let n = committee.n();
+    let i = committee.local_index;
+
+    let blueprint_id = ctx.blueprint_id()?;
+
+    // The execution ID is unique to this refresh job call
+    let (_, deterministic_hash) =
+        crate::compute_deterministic_hashes(n, blueprint_id, call_id, REFRESH_SALT);
+
+    // Every share must be refreshed, so every holder of one takes part
+    let holders = state.verification_shares.keys().copied().collect();
+    let parties = ctx.wait_for_holders(&committee, &holders).await?;
+
+    info!(
+        "Starting BLS Refresh for party {i}, n={n}, t={t}, keygen_call_id={keygen_call_id}, eid={}",
+        hex::encode(deterministic_hash)
+    );
+
+    let span = crate::telemetry::job_span("refresh", service_id, call_id, &deterministic_hash, i);
+
+    let network = SessionNetwork::<KeygenMsg>::new(
+        &ctx.sessions,
+        ctx.network_backend.clone(),
+        &parties,
+        crate::context::session_id(&deterministic_hash),
+    );
+
+    let party = round_based::party::MpcParty::connected(network);
+
+    let output =
+        crate::keygen_state_machine::bls_refresh_protocol(party, i, n, &state, ctx.round_timeout)
+            .instrument(span)
+            .await
+            .map_err(|e| BlsError::keygen(e, &committee.operators))?;
+
+    info!(
+        "Ending BLS Refresh for party {i}, n={n}, t={t}, eid={}",
+        hex::encode(deterministic_hash)
+    );
+
+    let public_key = output
+        .uncompressed_pk
+        .as_deref()
+        .ok_or_else(|| BlsError::Internal("Public key missing from refresh output".into()))?;
+    let public_key = output.scheme.encode_public_key(public_key, encoding)?;
+
+    // Only the shares change, anything updated while the refresh ran is kept.
+    // The refreshed share only fits the share the refresh started from.
+    ctx.store.update(&store_key, |current| {
+        let unchanged = match (&current.secret_key_bytes, &state.secret_key_bytes) {
+            (Some(current), Some(start)) => current.expose() == start.expose(),
+            _ => false,
+        };
+        if !unchanged {
+            return Err(BlsError::KeyBusy { keygen_call_id });
+        }
+        current.secret_key_bytes = output.secret_key_bytes;
+        current.verification_shares = output.verification_shares;
+        Ok::<_, BlsError>(())
+    })?;
+
+    Ok(TangleResult(RefreshResult {
+        public_key: public_key.into(),
+        ..Default::default()
+    }))
+}
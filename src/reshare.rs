@@ -0,0 +1,269 @@
+use crate::ReshareRequest;
+use crate::ReshareResult;
+use crate::ciphersuite::{KeyScheme, PointEncoding};
+use crate::context::{Committee, PARTY_READY_TIMEOUT, bls_ctx};
+use crate::error::BlsError;
+use crate::keygen_state_machine::{BlsState, KeygenMsg, ReshareDealer};
+use crate::session::SessionNetwork;
+use blueprint_sdk::alloy::primitives::Address;
+use blueprint_sdk::info;
+use blueprint_sdk::tangle::extract::{
+    BlockNumber, CallId, Caller, ServiceId, TangleArg, TangleResult,
+};
+use round_based::PartyIndex;
+use tracing::Instrument;
+
+const RESHARE_SALT: &str = "bls-reshare";
+
+/// Rounds of the reshare DKG, each bounded by the round timeout
+const RESHARE_ROUNDS: u32 = 4;
+
+/// Moves a previously generated key to the service's current operators.
+///
+/// Extracts keygen_call_id and the new threshold `t` from the on-chain
+/// request. Holders of the key's shares that are still in the service deal
+/// them into a DKG among the current operators, which must include at least
+/// the old threshold of them. Every operator must complete every round, and
+/// all of them check the reshared key against the requested public key
+/// before storing it. Only the key's owner may reshare it, so the owner
+/// carries over to the new committee, and keys without a recorded owner
+/// cannot be reshared. Delegated signers are not carried over and have to
+/// be set again. The group public key is unchanged and returned. Old shares
+/// are deleted once the new ones are stored, and by holders leaving the
+/// committee once the job has completed on chain.
+pub async fn reshare(
+    ServiceId(service_id): ServiceId,
+    CallId(call_id): CallId,
+    BlockNumber(block_number): BlockNumber,
+    Caller(caller): Caller,
+    TangleArg(request): TangleArg<ReshareRequest>,
+) -> Result<TangleResult<ReshareResult>, BlsError> {
+    let ctx = bls_ctx();
+    let keygen_call_id = request.keygen_call_id;
+    let t = request.t;
+    let scheme = KeyScheme::try_from(request.scheme)?;
+    let encoding = PointEncoding::try_from(request.encoding)?;
+    let caller = Address::from(caller);
+
+    // Held until the old share is replaced
+    let _key_guard = ctx.lock_key(keygen_call_id)?;
+
+    // This operator's share of the key, if it held one in the old committee
+    let old_entry = ctx.store.find_by_call_id(keygen_call_id)?;
+    if let Some((_, state)) = &old_entry {
+        if let Some(block) = state.retired_at_block {
+            return Err(BlsError::KeyRetired {
+                keygen_call_id,
+                block,
+            });
+        }
+        state.check_owner(caller)?;
+        if stored_public_key(state, encoding)? != request.public_key.as_ref() {
+            return Err(BlsError::InvalidRequest(format!(
+                "Public key does not match key {keygen_call_id}"
+            )));
+        }
+    }
+
+    // The new committee is the service's current operators
+    let operators = ctx.service_operators().await?;
+    let n = operators.len() as u16;
+
+    if t == 0 || t > n {
+        return Err(BlsError::InvalidRequest(format!(
+            "Invalid threshold t={t} for n={n}"
+        )));
+    }
+
+    // Holders of the old key in the new committee, by old committee index.
+    // Operators the keygen left out hold no share and cannot deal.
+    let dealers = old_entry.as_ref().map(|(_, state)| {
+        state
+            .verification_shares
+            .keys()
+            .copied()
+            .filter(|j| {
+                state
+                    .operators
+                    .get(usize::from(*j))
+                    .is_some_and(|operator| operators.contains(operator))
+            })
+            .collect::<Vec<PartyIndex>>()
+    });
+    if let (Some((_, state)), Some(dealers)) = (&old_entry, &dealers)
+        && dealers.len() < usize::from(state.t)
+    {
+        return Err(BlsError::PeerSetMismatch(format!(
+            "Only {} of the old committee remain, the key needs t={}",
+            dealers.len(),
+            state.t
+        )));
+    }
+
+    if !operators.contains(&ctx.operator_address) {
+        return match old_entry {
+            Some((old_key, state)) => {
+                leave(
+                    service_id,
+                    call_id,
+                    block_number,
+                    &old_key,
+                    &state,
+                    encoding,
+                )
+                .await
+            }
+            None => Err(BlsError::PeerSetMismatch(format!(
+                "Local operator {} is not in the committee",
+                ctx.operator_address
+            ))),
+        };
+    }
+
+    let committee = Committee::new(operators, ctx.operator_address)?;
+    let i = committee.local_index;
+
+    let blueprint_id = ctx.blueprint_id()?;
+
+    // The reshared key is stored under the new committee size
+    let (meta_hash, _) =
+        crate::compute_deterministic_hashes(n, blueprint_id, keygen_call_id, RESHARE_SALT);
+    // The execution ID is unique to this reshare job call
+    let (_, deterministic_hash) =
+        crate::compute_deterministic_hashes(n, blueprint_id, call_id, RESHARE_SALT);
+
+    let dealer = match (&old_entry, dealers) {
+        (Some((_, state)), Some(dealers)) => Some(ReshareDealer {
+            state,
+            old_index: Committee::new(state.operators.clone(), ctx.operator_address)?.local_index,
+            dealers,
+        }),
+        _ => None,
+    };
+
+    // Every operator of the new committee receives a share
+    let parties = ctx.wait_for_parties(&committee, usize::from(n) - 1).await?;
+
+    info!(
+        "Starting BLS Reshare for party {i}, n={n}, t={t}, keygen_call_id={keygen_call_id}, dealer={}, eid={}",
+        dealer.is_some(),
+        hex::encode(deterministic_hash)
+    );
+
+    let span = crate::telemetry::job_span("reshare", service_id, call_id, &deterministic_hash, i);
+
+    let network = SessionNetwork::<KeygenMsg>::new(
+        &ctx.sessions,
+        ctx.network_backend.clone(),
+        &parties,
+        crate::context::session_id(&deterministic_hash),
+    );
+
+    let party = round_based::party::MpcParty::connected(network);
+
+    let mut output = crate::keygen_state_machine::bls_reshare_protocol(
+        party,
+        i,
+        t,
+        n,
+        scheme,
+        dealer,
+        keygen_call_id,
+        ctx.round_timeout,
+    )
+    .instrument(span)
+    .await
+    .map_err(|e| BlsError::keygen(e, &committee.operators))?;
+
+    info!(
+        "Ending BLS Reshare for party {i}, n={n}, t={t}, eid={}",
+        hex::encode(deterministic_hash)
+    );
+
+    let public_key = output
+        .uncompressed_pk
+        .as_deref()
+        .ok_or_else(|| BlsError::Internal("Public key missing from reshare output".into()))?;
+    let public_key = output.scheme.encode_public_key(public_key, encoding)?;
+    // Operators without a share of the old key only learn it from the
+    // request
+    if public_key != request.public_key.as_ref() {
+        return Err(BlsError::Protocol(format!(
+            "Reshared public key does not match key {keygen_call_id}"
+        )));
+    }
+
+    output.operators = committee.operators;
+    output.created_at_block = block_number;
+    output.owner = caller;
+    output.policy = request.policy.into();
+    // Former holders keep what they signed with the key, including while
+    // the reshare ran
+    if let Some((old_key, _)) = &old_entry
+        && let Some(current) = ctx.store.get(old_key)?
+    {
+        output.policy_record = current.policy_record;
+    }
+
+    // Replace the old share with the new one
+    let store_key = hex::encode(meta_hash);
+    ctx.store.set(&store_key, output)?;
+    if let Some((old_key, _)) = old_entry
+        && old_key != store_key
+    {
+        ctx.store.remove(&old_key)?;
+    }
+
+    Ok(TangleResult(ReshareResult {
+        public_key: public_key.into(),
+        ..Default::default()
+    }))
+}
+
+/// Removes the share of a holder leaving the committee once the reshare
+/// job has completed on chain, so the key stays recoverable if it fails.
+/// The remaining holders deal the key without it.
+async fn leave(
+    service_id: u64,
+    call_id: u64,
+    block_number: u64,
+    old_key: &str,
+    state: &BlsState,
+    encoding: PointEncoding,
+) -> Result<TangleResult<ReshareResult>, BlsError> {
+    let ctx = bls_ctx();
+    let keygen_call_id = state.call_id;
+
+    info!(
+        "Leaving the committee of key {keygen_call_id}, waiting for reshare job {call_id} to complete"
+    );
+
+    let timeout = PARTY_READY_TIMEOUT + ctx.round_timeout * (RESHARE_ROUNDS + 1);
+    if !ctx
+        .wait_for_job_completion(service_id, call_id, block_number, timeout)
+        .await?
+    {
+        return Err(BlsError::Chain(format!(
+            "Reshare job {call_id} did not complete within {timeout:?}, keeping the share of key {keygen_call_id}"
+        )));
+    }
+
+    ctx.store.remove(old_key)?;
+    info!("Removed the share of key {keygen_call_id} after reshare job {call_id}");
+
+    let public_key = stored_public_key(state, encoding)?;
+
+    Ok(TangleResult(ReshareResult {
+        public_key: public_key.into(),
+        ..Default::default()
+    }))
+}
+
+/// Public key of the stored key `state` in `encoding`.
+fn stored_public_key(state: &BlsState, encoding: PointEncoding) -> Result<Vec<u8>, BlsError> {
+    let public_key = state
+        .uncompressed_pk
+        .as_deref()
+        .ok_or_else(|| BlsError::Internal("Public key missing from stored key".into()))?;
+    Ok(state.scheme.encode_public_key(public_key, encoding)?)
+}
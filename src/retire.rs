@@ -0,0 +1,55 @@
+use crate::context::bls_ctx;
+use crate::error::BlsError;
+use crate::{RetireKeyRequest, RetireKeyResult};
+use blueprint_sdk::alloy::primitives::Address;
+use blueprint_sdk::info;
+use blueprint_sdk::tangle::extract::{BlockNumber, CallId, Caller, TangleArg, TangleResult};
+
+/// Retires a previously generated key on request of its owner.
+///
+/// Each operator erases its secret share and keeps a tombstone with the
+/// key's public metadata in its place, so later jobs on the key fail with a
+/// "retired" error. Retiring an already retired key confirms the original
+/// retirement block.
+pub async fn retire_key(
+    CallId(call_id): CallId,
+    BlockNumber(block_number): BlockNumber,
+    Caller(caller): Caller,
+    TangleArg(request): TangleArg<RetireKeyRequest>,
+) -> Result<TangleResult<RetireKeyResult>, BlsError> {
+    let ctx = bls_ctx();
+    let keygen_call_id = request.keygen_call_id;
+    let caller = Address::from(caller);
+
+    let (store_key, state) = ctx
+        .store
+        .find_by_call_id(keygen_call_id)?
+        .ok_or(BlsError::KeyNotFound { keygen_call_id })?;
+
+    state.check_owner(caller)?;
+
+    if let Some(retired_at_block) = state.retired_at_block {
+        return Ok(TangleResult(RetireKeyResult {
+            keygen_call_id,
+            retired_at_block,
+            ..Default::default()
+        }));
+    }
+
+    // The share is zeroized when dropped, and the tombstone replaces the
+    // sealed copy on disk
+    ctx.store.update(&store_key, |state| {
+        state.secret_key_bytes = None;
+        state.encrypted_secret_key = None;
+        state.retired_at_block = Some(block_number);
+        Ok::<_, BlsError>(())
+    })?;
+
+    info!("Retired key {keygen_call_id} at block {block_number} in job {call_id}");
+
+    Ok(TangleResult(RetireKeyResult {
+        keygen_call_id,
+        retired_at_block: block_number,
+        ..Default::default()
+    }))
+}
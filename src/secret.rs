@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zeroize::Zeroize;
+
+/// A party's secret key share as big-endian scalar bytes.
+///
+/// The bytes are wiped when the value is dropped and never printed by `Debug`.
+/// Serializes as a plain byte vector, the same format as earlier stores.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SecretShare(Vec<u8>);
+
+impl SecretShare {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+
+    /// Borrows the raw share bytes. Callers must not copy them into
+    /// long-lived buffers.
+    pub fn expose(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Drop for SecretShare {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for SecretShare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretShare(<redacted>)")
+    }
+}
@@ -0,0 +1,358 @@
+use crate::keygen_state_machine::MessageKind;
+use crate::metrics::metrics;
+use blueprint_sdk::crypto::k256::K256Ecdsa;
+use blueprint_sdk::networking::service_handle::NetworkServiceHandle;
+use blueprint_sdk::networking::types::{MessageRouting, ProtocolMessage};
+use futures::{Sink, Stream};
+use libp2p::PeerId;
+use round_based::{Delivery, Incoming, MessageDestination, MessageType, Outgoing, PartyIndex};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::{debug, trace, warn};
+
+/// How often the dispatcher drains the network queue
+const DISPATCH_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long messages for a session that has not started locally are kept
+const PENDING_MESSAGE_TTL: Duration = Duration::from_secs(300);
+
+/// Upper bound on buffered messages across all pending sessions
+const MAX_PENDING_MESSAGES: usize = 4096;
+
+/// Wire format: every protocol message is tagged with its session ID.
+#[derive(Serialize, Deserialize)]
+struct SessionEnvelope<M> {
+    session: String,
+    msg: M,
+}
+
+/// Only the session tag, used by the dispatcher to route a message without
+/// knowing its protocol type.
+#[derive(Deserialize)]
+struct SessionTag {
+    session: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SessionError {
+    #[error("Failed to serialize message: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("Network error: {0}")]
+    Send(String),
+}
+
+#[derive(Default)]
+struct RouterState {
+    /// Sessions currently running locally
+    sessions: HashMap<String, mpsc::UnboundedSender<ProtocolMessage>>,
+    /// Messages received for sessions that have not started locally yet
+    pending: HashMap<String, Vec<(Instant, ProtocolMessage)>>,
+    pending_count: usize,
+}
+
+/// Routes incoming protocol messages to the session they belong to.
+///
+/// All protocol executions share one network queue. The router is its only
+/// consumer: messages for a running session are forwarded to it, messages for
+/// a session that has not started yet (a peer was faster to pick up the job)
+/// are buffered until it does or the TTL expires.
+#[derive(Default)]
+pub struct SessionRouter {
+    state: Mutex<RouterState>,
+}
+
+impl SessionRouter {
+    /// Starts the dispatcher on the given network handle.
+    pub fn spawn(mut handle: NetworkServiceHandle<K256Ecdsa>) -> Arc<Self> {
+        let router = Arc::new(Self::default());
+        let dispatcher = router.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(DISPATCH_INTERVAL);
+            loop {
+                while let Some(message) = handle.next_protocol_message() {
+                    dispatcher.dispatch(message);
+                }
+                dispatcher.prune_pending();
+                interval.tick().await;
+            }
+        });
+        router
+    }
+
+    fn dispatch(&self, message: ProtocolMessage) {
+        let Ok(SessionTag { session }) = serde_json::from_slice(&message.payload) else {
+            debug!(%message, "Dropping message without a session tag");
+            return;
+        };
+
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(tx) = state.sessions.get(&session) {
+            if tx.send(message).is_err() {
+                state.sessions.remove(&session);
+            }
+            return;
+        }
+
+        trace!(%session, "Buffering message for session not yet started");
+        if state.pending_count >= MAX_PENDING_MESSAGES {
+            debug!(%session, "Pending message buffer full; dropping message");
+            return;
+        }
+        state
+            .pending
+            .entry(session)
+            .or_default()
+            .push((Instant::now(), message));
+        state.pending_count += 1;
+    }
+
+    fn prune_pending(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let mut dropped = 0;
+        state.pending.retain(|_, messages| {
+            let before = messages.len();
+            messages.retain(|(received, _)| now.duration_since(*received) < PENDING_MESSAGE_TTL);
+            dropped += before - messages.len();
+            !messages.is_empty()
+        });
+        state.pending_count -= dropped;
+    }
+
+    /// Registers a session and returns the stream of its messages, starting
+    /// with any that arrived before it was registered.
+    fn register(self: &Arc<Self>, session: &str) -> SessionReceiver {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(messages) = state.pending.remove(session) {
+            state.pending_count -= messages.len();
+            for (_, message) in messages {
+                let _ = tx.send(message);
+            }
+        }
+        state.sessions.insert(session.to_string(), tx);
+
+        SessionReceiver {
+            router: self.clone(),
+            session: session.to_string(),
+            rx,
+        }
+    }
+
+    fn unregister(&self, session: &str) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.sessions.remove(session);
+    }
+}
+
+/// Unregisters the session from the router when dropped.
+struct SessionReceiver {
+    router: Arc<SessionRouter>,
+    session: String,
+    rx: mpsc::UnboundedReceiver<ProtocolMessage>,
+}
+
+impl Drop for SessionReceiver {
+    fn drop(&mut self) {
+        self.router.unregister(&self.session);
+    }
+}
+
+/// Round-based delivery scoped to a single protocol execution.
+pub struct SessionNetwork<M> {
+    handle: NetworkServiceHandle<K256Ecdsa>,
+    receiver: SessionReceiver,
+    parties: Arc<HashMap<PartyIndex, PeerId>>,
+    _phantom: std::marker::PhantomData<M>,
+}
+
+impl<M> SessionNetwork<M> {
+    /// Joins `session` on the router. Must be created before the protocol
+    /// starts sending so no message of the session is missed.
+    pub fn new(
+        router: &Arc<SessionRouter>,
+        handle: NetworkServiceHandle<K256Ecdsa>,
+        parties: &HashMap<PartyIndex, PeerId>,
+        session: String,
+    ) -> Self {
+        Self {
+            receiver: router.register(&session),
+            handle,
+            parties: Arc::new(parties.clone()),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<M> Delivery<M> for SessionNetwork<M>
+where
+    M: Serialize + DeserializeOwned + round_based::ProtocolMessage + MessageKind + Unpin,
+{
+    type Send = SessionSender<M>;
+    type Receive = SessionStream<M>;
+    type SendError = SessionError;
+    type ReceiveError = SessionError;
+
+    fn split(self) -> (Self::Receive, Self::Send) {
+        let peer_to_party = self
+            .parties
+            .iter()
+            .map(|(idx, peer_id)| (*peer_id, *idx))
+            .collect();
+
+        let sender = SessionSender {
+            handle: self.handle,
+            session: self.receiver.session.clone(),
+            parties: self.parties,
+            next_msg_id: 0,
+            _phantom: std::marker::PhantomData,
+        };
+        let stream = SessionStream {
+            receiver: self.receiver,
+            peer_to_party,
+            _phantom: std::marker::PhantomData,
+        };
+
+        (stream, sender)
+    }
+}
+
+pub struct SessionSender<M> {
+    handle: NetworkServiceHandle<K256Ecdsa>,
+    session: String,
+    parties: Arc<HashMap<PartyIndex, PeerId>>,
+    next_msg_id: u64,
+    _phantom: std::marker::PhantomData<M>,
+}
+
+impl<M> Sink<Outgoing<M>> for SessionSender<M>
+where
+    M: Serialize + round_based::ProtocolMessage + MessageKind + Unpin,
+{
+    type Error = SessionError;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, outgoing: Outgoing<M>) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        let message_id = this.next_msg_id;
+        this.next_msg_id += 1;
+
+        let recipient = match outgoing.recipient {
+            MessageDestination::AllParties => None,
+            MessageDestination::OneParty(p) => Some(
+                this.parties
+                    .get(&p)
+                    .copied()
+                    .ok_or_else(|| SessionError::Send(format!("Party {p} is not connected")))?,
+            ),
+        };
+
+        let routing = MessageRouting {
+            message_id,
+            round_id: outgoing.msg.round(),
+            sender: this.handle.local_peer_id,
+            recipient,
+        };
+        let payload = serde_json::to_vec(&SessionEnvelope {
+            session: this.session.clone(),
+            msg: &outgoing.msg,
+        })?;
+        metrics().count_message("sent", outgoing.msg.kind(), payload.len());
+        debug!(
+            session = %this.session,
+            round = outgoing.msg.round() + 1,
+            message = outgoing.msg.kind(),
+            recipient = ?outgoing.recipient,
+            bytes = payload.len(),
+            "Sending protocol message",
+        );
+
+        this.handle
+            .send(routing, payload)
+            .map_err(SessionError::Send)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+pub struct SessionStream<M> {
+    receiver: SessionReceiver,
+    peer_to_party: HashMap<PeerId, PartyIndex>,
+    _phantom: std::marker::PhantomData<M>,
+}
+
+impl<M> Stream for SessionStream<M>
+where
+    M: DeserializeOwned + round_based::ProtocolMessage + MessageKind + Unpin,
+{
+    type Item = Result<Incoming<M>, SessionError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            let Some(message) = futures::ready!(this.receiver.rx.poll_recv(cx)) else {
+                return Poll::Ready(None);
+            };
+
+            let Some(sender) = this.peer_to_party.get(&message.routing.sender).copied() else {
+                trace!(
+                    session = %this.receiver.session,
+                    sender = %message.routing.sender,
+                    "Ignoring message from a peer outside the session",
+                );
+                continue;
+            };
+
+            let msg_type = if message.routing.recipient.is_some() {
+                MessageType::P2P
+            } else {
+                MessageType::Broadcast
+            };
+
+            // A single malformed message must not abort the whole protocol
+            let envelope: SessionEnvelope<M> = match serde_json::from_slice(&message.payload) {
+                Ok(envelope) => envelope,
+                Err(err) => {
+                    warn!(
+                        session = %this.receiver.session,
+                        sender,
+                        bytes = message.payload.len(),
+                        error = %err,
+                        "Dropping malformed protocol message",
+                    );
+                    continue;
+                }
+            };
+            metrics().count_message("received", envelope.msg.kind(), message.payload.len());
+            debug!(
+                session = %this.receiver.session,
+                round = envelope.msg.round() + 1,
+                message = envelope.msg.kind(),
+                sender,
+                bytes = message.payload.len(),
+                "Received protocol message",
+            );
+            return Poll::Ready(Some(Ok(Incoming {
+                id: message.routing.message_id,
+                sender,
+                msg_type,
+                msg: envelope.msg,
+            })));
+        }
+    }
+}
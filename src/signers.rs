@@ -0,0 +1,70 @@
+use crate::context::bls_ctx;
+use crate::error::BlsError;
+use crate::{SetSignersRequest, SetSignersResult};
+use blueprint_sdk::alloy::primitives::Address;
+use blueprint_sdk::info;
+use blueprint_sdk::tangle::extract::{CallId, Caller, TangleArg, TangleResult};
+
+/// Most delegated signers a key may have
+pub const MAX_SIGNERS: usize = 64;
+
+/// Replaces the delegated signers of a previously generated key on request
+/// of its owner.
+///
+/// Delegated signers may request signatures with the key like its owner,
+/// but not manage or retire it. An empty list revokes all delegations.
+pub async fn set_signers(
+    CallId(call_id): CallId,
+    Caller(caller): Caller,
+    TangleArg(request): TangleArg<SetSignersRequest>,
+) -> Result<TangleResult<SetSignersResult>, BlsError> {
+    let ctx = bls_ctx();
+    let keygen_call_id = request.keygen_call_id;
+
+    let (store_key, state) = ctx
+        .store
+        .find_by_call_id(keygen_call_id)?
+        .ok_or(BlsError::KeyNotFound { keygen_call_id })?;
+
+    state.check_owner(Address::from(caller))?;
+    if let Some(block) = state.retired_at_block {
+        return Err(BlsError::KeyRetired {
+            keygen_call_id,
+            block,
+        });
+    }
+
+    let mut signers = Vec::with_capacity(request.signers.len());
+    for signer in request.signers {
+        if signer.is_zero() {
+            return Err(BlsError::InvalidRequest(
+                "The zero address cannot be a signer".into(),
+            ));
+        }
+        if !signers.contains(&signer) {
+            signers.push(signer);
+        }
+    }
+    if signers.len() > MAX_SIGNERS {
+        return Err(BlsError::InvalidRequest(format!(
+            "{} signers exceed the limit of {MAX_SIGNERS}",
+            signers.len()
+        )));
+    }
+
+    ctx.store.update(&store_key, |state| {
+        state.signers = signers.clone();
+        Ok::<_, BlsError>(())
+    })?;
+
+    info!(
+        "Set {} delegated signers for key {keygen_call_id} in job {call_id}",
+        signers.len()
+    );
+
+    Ok(TangleResult(SetSignersResult {
+        keygen_call_id,
+        signers,
+        ..Default::default()
+    }))
+}
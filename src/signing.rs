@@ -1,107 +1,343 @@
-use crate::SignRequest;
-use crate::SignResult;
-use crate::context::bls_ctx;
+use crate::ciphersuite::{Ciphersuite, PointEncoding, SigningDomain};
+use crate::context::{Committee, bls_ctx};
+use crate::error::{BlsError, operator_addresses};
+use crate::keygen_state_machine::{BlsState, compressed_public_key};
+use crate::policy::{self, PolicyRequest};
+use crate::session::SessionNetwork;
 use crate::signing_state_machine::SigningMsg;
-use blueprint_sdk::crypto::k256::K256Ecdsa;
-use blueprint_sdk::info;
-use blueprint_sdk::networking::round_based_compat::RoundBasedNetworkAdapter;
-use blueprint_sdk::tangle::extract::{Caller, TangleArg, TangleResult};
+use crate::{
+    ProvePossessionRequest, ProvePossessionResult, SignBatchRequest, SignBatchResult, SignRequest,
+    SignResult,
+};
+use blueprint_sdk::alloy::primitives::Address;
+use blueprint_sdk::tangle::extract::{
+    BlockNumber, CallId, Caller, ServiceId, TangleArg, TangleResult,
+};
+use blueprint_sdk::{info, warn};
 use round_based::PartyIndex;
-use std::collections::HashMap;
+use tracing::Instrument;
 
 const SIGNING_SALT: &str = "bls-signing";
 
+/// Most messages a single batch signing job may sign
+pub const MAX_SIGN_BATCH: usize = 256;
+
 #[derive(Debug, thiserror::Error)]
 pub enum SigningError {
-    #[error("Context error: {0}")]
-    ContextError(String),
     #[error("Key retrieval error: {0}")]
     KeyRetrievalError(String),
     #[error("MPC error: {0}")]
     MpcError(String),
-}
-
-impl From<SigningError> for String {
-    fn from(err: SigningError) -> Self {
-        err.to_string()
-    }
+    #[error("Caller {caller} is neither the owner of key {keygen_call_id} nor a delegated signer")]
+    Unauthorized {
+        caller: Address,
+        keygen_call_id: u64,
+    },
+    #[error("Message {index} does not start with the key's required prefix")]
+    MessagePrefixMismatch { index: usize },
+    #[error("Message {index} is {len} bytes, the key's policy allows at most {max}")]
+    MessageTooLong { index: usize, len: usize, max: u32 },
+    #[error("The key's policy allows at most {max} signatures in block {block}")]
+    RateLimited { block: u64, max: u32 },
+    #[error("Message {index} is too short to hold a slot and domain")]
+    MissingSlotDomain { index: usize },
+    #[error(
+        "Message {index} differs from the one already signed for slot {slot} and domain {domain}"
+    )]
+    SlashableMessage {
+        index: usize,
+        slot: u64,
+        domain: String,
+    },
+    #[error("Message {index} is for slot {slot}, below the oldest slot still remembered, {oldest}")]
+    SlotTooOld {
+        index: usize,
+        slot: u64,
+        oldest: u64,
+    },
+    #[error("Refused by signing policy: {0}")]
+    PolicyRefused(String),
+    #[error(
+        "Only {valid} valid signature shares, need t={t}; invalid shares from parties {offenders:?}"
+    )]
+    InvalidShares {
+        valid: usize,
+        t: u16,
+        offenders: Vec<PartyIndex>,
+    },
 }
 
 /// Signs a message using the BLS protocol with a previously generated key.
 ///
 /// Extracts keygen_call_id and message from the on-chain request, retrieves
 /// the stored key share, runs the signing protocol, and returns the signature.
+/// Only the key's owner and its delegated signers may request signatures,
+/// and only for messages the key's signing policy allows.
 pub async fn sign(
-    Caller(_caller): Caller,
+    ServiceId(service_id): ServiceId,
+    CallId(call_id): CallId,
+    BlockNumber(block_number): BlockNumber,
+    Caller(caller): Caller,
     TangleArg(request): TangleArg<SignRequest>,
-) -> Result<TangleResult<SignResult>, String> {
-    let ctx = bls_ctx();
-    let keygen_call_id = request.keygen_call_id;
-    let message: Vec<u8> = request.message.to_vec();
-
-    // Get party info from connected peers
-    let mut all_peers = ctx.network_backend.peers();
-    let local_peer_id = ctx.network_backend.local_peer_id;
-    if !all_peers.contains(&local_peer_id) {
-        all_peers.push(local_peer_id);
+) -> Result<TangleResult<SignResult>, BlsError> {
+    let encoding = PointEncoding::try_from(request.encoding)?;
+    let (mut signatures, offenders) = run_signing(
+        "sign",
+        service_id,
+        call_id,
+        caller.into(),
+        request.keygen_call_id,
+        encoding,
+        Some(block_number),
+        |state| {
+            let domain = SigningDomain::new(
+                state.scheme,
+                Ciphersuite::try_from(request.ciphersuite)?,
+                &request.dst,
+            )?;
+            Ok((domain, vec![request.message.to_vec()]))
+        },
+    )
+    .await?;
+
+    let signature = signatures
+        .pop()
+        .ok_or_else(|| BlsError::Internal("Signature not found in signing output".into()))?;
+
+    Ok(TangleResult(SignResult {
+        signature: signature.into(),
+        offenders,
+        ..Default::default()
+    }))
+}
+
+/// Signs a batch of messages with a previously generated key in one
+/// protocol run, returning one signature per message in request order.
+pub async fn sign_batch(
+    ServiceId(service_id): ServiceId,
+    CallId(call_id): CallId,
+    BlockNumber(block_number): BlockNumber,
+    Caller(caller): Caller,
+    TangleArg(request): TangleArg<SignBatchRequest>,
+) -> Result<TangleResult<SignBatchResult>, BlsError> {
+    if request.messages.is_empty() {
+        return Err(BlsError::InvalidRequest(
+            "Batch signing request has no messages".into(),
+        ));
+    }
+    if request.messages.len() > MAX_SIGN_BATCH {
+        return Err(BlsError::InvalidRequest(format!(
+            "Batch of {} messages exceeds the limit of {MAX_SIGN_BATCH}",
+            request.messages.len()
+        )));
     }
-    all_peers.sort();
 
-    let n = all_peers.len() as u16;
-    let i = all_peers
-        .iter()
-        .position(|p| *p == local_peer_id)
-        .ok_or_else(|| "Local peer not found in peer list".to_string())? as u16;
+    let encoding = PointEncoding::try_from(request.encoding)?;
+    let (signatures, offenders) = run_signing(
+        "sign_batch",
+        service_id,
+        call_id,
+        caller.into(),
+        request.keygen_call_id,
+        encoding,
+        Some(block_number),
+        |state| {
+            let domain = SigningDomain::new(
+                state.scheme,
+                Ciphersuite::try_from(request.ciphersuite)?,
+                &request.dst,
+            )?;
+            let messages = request
+                .messages
+                .iter()
+                .map(|message| message.to_vec())
+                .collect();
+            Ok((domain, messages))
+        },
+    )
+    .await?;
 
-    let parties: HashMap<PartyIndex, libp2p::PeerId> = all_peers
-        .into_iter()
-        .enumerate()
-        .map(|(idx, peer_id)| (idx as PartyIndex, peer_id))
-        .collect();
+    Ok(TangleResult(SignBatchResult {
+        signatures: signatures.into_iter().map(Into::into).collect(),
+        offenders,
+        ..Default::default()
+    }))
+}
 
-    let blueprint_id = ctx.blueprint_id()?;
+/// Produces a proof of possession of a previously generated key: a
+/// threshold signature over the compressed public key under the POP
+/// ciphersuite's proof DST.
+pub async fn prove_possession(
+    ServiceId(service_id): ServiceId,
+    CallId(call_id): CallId,
+    Caller(caller): Caller,
+    TangleArg(request): TangleArg<ProvePossessionRequest>,
+) -> Result<TangleResult<ProvePossessionResult>, BlsError> {
+    let encoding = PointEncoding::try_from(request.encoding)?;
+    let mut public_key = Vec::new();
+    let (mut signatures, offenders) = run_signing(
+        "prove_possession",
+        service_id,
+        call_id,
+        caller.into(),
+        request.keygen_call_id,
+        encoding,
+        None,
+        |state| {
+            let uncompressed_pk = state
+                .uncompressed_pk
+                .as_deref()
+                .ok_or_else(|| BlsError::Internal("Public key not found in key entry".into()))?;
+            public_key = state.scheme.encode_public_key(uncompressed_pk, encoding)?;
+            let compressed =
+                compressed_public_key(state).map_err(|e| BlsError::keygen(e, &state.operators))?;
+            Ok((
+                SigningDomain::proof_of_possession(state.scheme),
+                vec![compressed],
+            ))
+        },
+    )
+    .await?;
 
-    // Compute hash for key retrieval — must use the call_id of the keygen job
-    let (meta_hash, deterministic_hash) =
-        crate::compute_deterministic_hashes(n, blueprint_id, keygen_call_id, SIGNING_SALT);
+    let proof_of_possession = signatures.pop().ok_or_else(|| {
+        BlsError::Internal("Proof of possession not found in signing output".into())
+    })?;
 
-    // Retrieve the key entry
-    let store_key = hex::encode(meta_hash);
-    let state = ctx
+    Ok(TangleResult(ProvePossessionResult {
+        public_key: public_key.into(),
+        proof_of_possession: proof_of_possession.into(),
+        offenders,
+        ..Default::default()
+    }))
+}
+
+/// Runs the signing protocol with the key from `keygen_call_id`, if `caller`
+/// may sign with it, over the domain and messages `prepare` derives from the
+/// key entry, returning the verified signatures in `encoding` and the
+/// operators whose shares were dropped.
+///
+/// With a `policy_block`, the messages must pass the key's signing policy at
+/// that block before this operator releases its shares. Proofs of
+/// possession, which sign the key itself, pass `None`. The protocol runs in
+/// a span named after `job`.
+#[allow(clippy::too_many_arguments)]
+async fn run_signing<F>(
+    job: &'static str,
+    service_id: u64,
+    call_id: u64,
+    caller: Address,
+    keygen_call_id: u64,
+    encoding: PointEncoding,
+    policy_block: Option<u64>,
+    prepare: F,
+) -> Result<(Vec<Vec<u8>>, Vec<Address>), BlsError>
+where
+    F: FnOnce(&BlsState) -> Result<(SigningDomain, Vec<Vec<u8>>), BlsError>,
+{
+    let ctx = bls_ctx();
+
+    // Looked up by call ID, as the committee may have changed since keygen
+    let (store_key, state) = ctx
         .store
-        .get(&store_key)
-        .map_err(|e| format!("Store error: {e}"))?
-        .ok_or_else(|| "Key entry not found for keygen_call_id".to_string())?;
+        .find_by_call_id(keygen_call_id)?
+        .ok_or(BlsError::KeyNotFound { keygen_call_id })?;
+
+    if let Some(block) = state.retired_at_block {
+        return Err(BlsError::KeyRetired {
+            keygen_call_id,
+            block,
+        });
+    }
+    if !state.is_authorized_signer(caller) {
+        return Err(BlsError::signing(
+            SigningError::Unauthorized {
+                caller,
+                keygen_call_id,
+            },
+            &state.operators,
+        ));
+    }
+
+    // Use the committee persisted at keygen so party indices match the shares
+    let committee = Committee::new(state.operators.clone(), ctx.operator_address)?;
+    let n = committee.n();
+    let i = committee.local_index;
+
+    let blueprint_id = ctx.blueprint_id()?;
+
+    // The execution ID is unique to this signing job call
+    let (_, deterministic_hash) =
+        crate::compute_deterministic_hashes(n, blueprint_id, call_id, SIGNING_SALT);
+
+    let (domain, messages) = prepare(&state)?;
+
+    // Checked and recorded before any share leaves this operator
+    if let Some(block_number) = policy_block {
+        let request = PolicyRequest {
+            keygen_call_id,
+            caller,
+            block_number,
+            messages: &messages,
+        };
+        ctx.store.update(&store_key, |state| {
+            policy::enforce(state, &ctx.policy_hooks, &request)
+                .map_err(|e| BlsError::signing(e, &state.operators))
+        })?;
+    }
 
     let t = state.t;
 
+    // Only `t` signature shares are needed
+    let parties = ctx
+        .wait_for_parties(&committee, usize::from(t).saturating_sub(1))
+        .await?;
+
     info!(
-        "Starting BLS Signing for party {i}, n={n}, t={t}, eid={}",
+        "Starting BLS Signing for party {i}, n={n}, t={t}, keygen_call_id={keygen_call_id}, messages={}, eid={}",
+        messages.len(),
         hex::encode(deterministic_hash)
     );
 
-    let network = RoundBasedNetworkAdapter::<SigningMsg, K256Ecdsa>::new(
+    let span = crate::telemetry::job_span(job, service_id, call_id, &deterministic_hash, i);
+
+    let network = SessionNetwork::<SigningMsg>::new(
+        &ctx.sessions,
         ctx.network_backend.clone(),
-        i,
         &parties,
-        crate::context::NETWORK_PROTOCOL,
+        crate::context::session_id(&deterministic_hash),
     );
 
     let party = round_based::party::MpcParty::connected(network);
 
     let output =
-        crate::signing_state_machine::bls_signing_protocol(party, i, n, &state, message).await?;
+        crate::signing_state_machine::bls_signing_protocol(party, i, n, &state, &domain, &messages)
+            .instrument(span)
+            .await
+            .map_err(|e| BlsError::signing(e, &state.operators))?;
 
     info!(
         "Ending BLS Signing for party {i}, n={n}, t={t}, eid={}",
         hex::encode(deterministic_hash)
     );
 
-    let signature = output
-        .signature
-        .ok_or_else(|| "Signature not found in signing output".to_string())?;
+    if output.signatures.len() != messages.len() {
+        return Err(BlsError::Protocol(format!(
+            "Signing produced {} signatures for {} messages",
+            output.signatures.len(),
+            messages.len()
+        )));
+    }
 
-    Ok(TangleResult(SignResult {
-        signature: signature.into(),
-    }))
+    let offenders = operator_addresses(&state.operators, &output.invalid_shares);
+    if !offenders.is_empty() {
+        warn!("Signing job {call_id} dropped invalid signature shares from {offenders:?}");
+    }
+
+    let signatures = output
+        .signatures
+        .iter()
+        .map(|signature| state.scheme.encode_signature(signature, encoding))
+        .collect::<Result<_, _>>()?;
+
+    Ok((signatures, offenders))
 }
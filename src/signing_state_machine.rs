@@ -1,20 +1,28 @@
-use blueprint_sdk::crypto::hashing::sha2_256;
-use itertools::Itertools;
-use round_based::MessageDestination;
-use round_based::rounds_router::{RoundsRouter, simple_store::RoundInput};
-use round_based::{Delivery, Mpc, MpcParty, PartyIndex, ProtocolMessage};
+use blsful::inner_types::{Field, G1Projective, G2Projective, Group, Scalar};
+use chacha20poly1305::aead::OsRng;
+use round_based::rounds_router::simple_store::RoundInputError;
+use round_based::rounds_router::{MessagesStore, RoundsRouter};
+use round_based::{Delivery, Incoming, MessageType, Mpc, MpcParty, MsgId, PartyIndex};
+use round_based::{MessageDestination, ProtocolMessage};
 use serde::{Deserialize, Serialize};
-use snowbridge_milagro_bls::{PublicKey, SecretKey, Signature};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use tracing::warn;
+use zeroize::Zeroizing;
 
-use crate::keygen_state_machine::{BlsState, HasRecipient};
+use crate::ciphersuite::{KeyGroup, KeyScheme, SigningDomain};
+use crate::keygen_state_machine::{
+    BlsState, HasRecipient, MessageKind, group_public_key, lagrange_coefficient, participant_id,
+    point_from_bytes, share_scalar,
+};
+use crate::metrics::metrics;
 use crate::signing::SigningError;
 
 #[derive(Default, Clone)]
 pub struct BlsSigningState {
-    pub secret_key: Option<SecretKey>,
-    pub signature: Option<Vec<u8>>,
-    received_sig_shares: BTreeMap<usize, Signature>,
+    /// One signature per message, in request order
+    pub signatures: Vec<Vec<u8>>,
+    /// Parties whose signature share failed verification and was dropped
+    pub invalid_shares: Vec<PartyIndex>,
 }
 
 #[derive(ProtocolMessage, Serialize, Deserialize, Clone)]
@@ -26,7 +34,7 @@ pub enum SigningMsg {
 pub struct Msg1 {
     pub sender: u16,
     pub receiver: Option<u16>,
-    pub body: Vec<u8>, // signature_share
+    pub shares: Vec<Vec<u8>>, // one signature share per message
 }
 
 impl HasRecipient for SigningMsg {
@@ -37,37 +45,84 @@ impl HasRecipient for SigningMsg {
     }
 }
 
+impl MessageKind for SigningMsg {
+    fn kind(&self) -> &'static str {
+        match self {
+            SigningMsg::Round1Broadcast(..) => "Round1Broadcast",
+        }
+    }
+}
+
+/// Signs every message in `input_data_to_sign` in a single broadcast round.
+#[tracing::instrument(skip_all, fields(party = i, t = state.t, n, messages = input_data_to_sign.len()))]
 pub async fn bls_signing_protocol<M, T>(
     party: M,
     i: PartyIndex,
     n: u16,
     state: &BlsState,
-    input_data_to_sign: T,
+    domain: &SigningDomain,
+    input_data_to_sign: &[T],
 ) -> Result<BlsSigningState, SigningError>
 where
     M: Mpc<ProtocolMessage = SigningMsg>,
     T: AsRef<[u8]>,
+{
+    let messages = input_data_to_sign
+        .iter()
+        .map(AsRef::as_ref)
+        .collect::<Vec<_>>();
+    if messages.is_empty() {
+        return Err(SigningError::MpcError("No messages to sign".to_string()));
+    }
+    match state.scheme {
+        KeyScheme::MinimalPublicKeySize => {
+            sign::<M, G1Projective>(party, i, n, state, domain, &messages).await
+        }
+        KeyScheme::MinimalSignatureSize => {
+            sign::<M, G2Projective>(party, i, n, state, domain, &messages).await
+        }
+    }
+}
+
+async fn sign<M, G>(
+    party: M,
+    i: PartyIndex,
+    n: u16,
+    state: &BlsState,
+    domain: &SigningDomain,
+    messages: &[&[u8]],
+) -> Result<BlsSigningState, SigningError>
+where
+    M: Mpc<ProtocolMessage = SigningMsg>,
+    G: KeyGroup,
 {
     let MpcParty { delivery, .. } = party.into_party();
     let (incomings, mut outgoings) = delivery.split();
     let mut signing_state = BlsSigningState::default();
+    let round_started = std::time::Instant::now();
 
-    // Extract secret key bytes from state
-    let secret_key_bytes = state.secret_key_bytes.as_ref().ok_or_else(|| {
-        SigningError::KeyRetrievalError("Secret key not found in state".to_string())
-    })?;
-
-    let secret_key = SecretKey::from_bytes(secret_key_bytes)
-        .map_err(|e| SigningError::MpcError(format!("Failed to create secret key: {e:?}")))?;
-
-    // Step 1: Generate shares
-    let sign_input = sha2_256(input_data_to_sign.as_ref());
-    let sig_share = Signature::new(&sign_input, &secret_key);
+    // Step 1: Generate shares. The secret key only lives for this step and
+    // is wiped when dropped.
+    let group_pk =
+        group_public_key::<G>(state).map_err(|e| SigningError::KeyRetrievalError(e.to_string()))?;
+    let hashed_messages = messages
+        .iter()
+        .map(|message| domain.hash_to_point(group_pk, message))
+        .collect::<Vec<_>>();
+    let sig_shares = {
+        let secret_key = Zeroizing::new(
+            share_scalar(state).map_err(|e| SigningError::KeyRetrievalError(e.to_string()))?,
+        );
+        hashed_messages
+            .iter()
+            .map(|hashed_message| (*hashed_message * *secret_key).to_compressed())
+            .collect()
+    };
 
     let my_msg = Msg1 {
         sender: i,
         receiver: None,
-        body: sig_share.as_bytes().to_vec(),
+        shares: sig_shares,
     };
     // Step 2: Broadcast shares
     let msg = SigningMsg::Round1Broadcast(my_msg.clone());
@@ -76,58 +131,263 @@ where
         .await
         .map_err(|e| SigningError::MpcError(e.to_string()))?;
 
-    // Step 3: Receive shares until there are t+1 total
+    // Step 3: Receive shares until there are t valid ones (including our own).
+    // Each party's shares are checked against its verification share, and
+    // a party with any invalid share is dropped and another awaited instead.
+    // Parties without a verification share hold no share of the key.
+    let verification_keys = state
+        .verification_shares
+        .iter()
+        .map(|(j, vk)| {
+            point_from_bytes::<G>(vk)
+                .map(|pk| (*j, pk))
+                .map_err(|e| SigningError::MpcError(format!("Invalid verification share: {e}")))
+        })
+        .collect::<Result<BTreeMap<_, _>, _>>()?;
+    let holders = verification_keys.keys().copied().collect();
+    let expected_hashes = hashed_messages.clone();
+    let validate = move |sender: PartyIndex, msg: &Msg1| {
+        verify_sig_shares::<G>(
+            &msg.shares,
+            verification_keys.get(&sender),
+            &expected_hashes,
+        )
+    };
+
     let mut rounds = RoundsRouter::builder();
-    let round = rounds.add_round(RoundInput::<Msg1>::broadcast(i, n));
+    let round = rounds.add_round(ThresholdRoundInput::<Msg1>::new(
+        i, n, state.t, holders, validate,
+    ));
     let mut rounds = rounds.listen(incomings);
 
-    let msgs = rounds
+    let ThresholdOutput {
+        mut messages,
+        rejected,
+    } = rounds
         .complete(round)
         .await
         .map_err(|e| SigningError::MpcError(format!("Failed to complete round: {e}")))?;
+    metrics().observe_round("signing", 1, round_started.elapsed());
+    for (sender, reason) in &rejected {
+        warn!("[BLS-SIGN] Dropping signature share from party {sender}: {reason}");
+    }
+    signing_state.invalid_shares = rejected.into_keys().collect();
 
-    for msg in msgs.into_vec_including_me(my_msg) {
-        let (sender, sig) = (msg.sender, msg.body);
-        let sig = Signature::from_bytes(&sig)
-            .map_err(|e| SigningError::MpcError(format!("Failed to create signature: {e:?}")))?;
-        signing_state
-            .received_sig_shares
-            .insert(sender as usize, sig);
+    messages.insert(i, my_msg);
+    if messages.len() < usize::from(state.t) {
+        return Err(SigningError::InvalidShares {
+            valid: messages.len(),
+            t: state.t,
+            offenders: signing_state.invalid_shares,
+        });
     }
 
-    // Step 4: Verify the combined signatures and public keys
-    let sig_shares = signing_state
-        .received_sig_shares
-        .clone()
-        .into_iter()
-        .sorted_by_key(|r| r.0)
-        .map(|r| r.1)
-        .collect::<Vec<_>>();
+    let mut sig_shares = BTreeMap::new();
+    for (sender, msg) in messages.into_iter().take(state.t as usize) {
+        let shares = msg
+            .shares
+            .iter()
+            .map(|share| sig_share_from_bytes::<G>(share))
+            .collect::<Result<Vec<_>, _>>()?;
+        sig_shares.insert(sender, shares);
+    }
 
-    let combined_signature = snowbridge_milagro_bls::AggregateSignature::aggregate(
-        &sig_shares.iter().collect::<Vec<_>>(),
-    );
+    // Step 4: Combine the shares with Lagrange coefficients over the DKG identifiers
+    let combined_signatures = combine_sig_shares::<G>(&sig_shares, hashed_messages.len())?;
 
-    let uncompressed_pk = state.uncompressed_pk.as_ref().ok_or_else(|| {
-        SigningError::KeyRetrievalError("Uncompressed public key not found in state".to_string())
-    })?;
+    for (index, (hashed_message, signature)) in
+        hashed_messages.iter().zip(&combined_signatures).enumerate()
+    {
+        if !group_pk.core_verify(*hashed_message, *signature) {
+            return Err(SigningError::MpcError(format!(
+                "Failed to verify signature {index} locally"
+            )));
+        }
+    }
 
-    let as_pk = PublicKey::from_uncompressed_bytes(&uncompressed_pk[1..])
-        .map_err(|e| SigningError::MpcError(format!("Failed to create public key: {e:?}")))?;
+    signing_state.signatures = combined_signatures
+        .iter()
+        .map(KeyGroup::to_compressed)
+        .collect();
 
-    let as_sig = Signature::from_bytes(&combined_signature.as_bytes())
-        .map_err(|e| SigningError::MpcError(format!("Failed to create signature: {e:?}")))?;
+    Ok(signing_state)
+}
 
-    if !as_sig.verify(&sign_input, &as_pk) {
-        return Err(SigningError::MpcError(
-            "Failed to verify signature locally".to_string(),
+/// Checks a party's signature shares, one per message, against its public
+/// verification share.
+///
+/// The shares are checked at once on a random linear combination, which
+/// only holds if every share is valid (up to negligible probability).
+fn verify_sig_shares<G: KeyGroup>(
+    shares: &[Vec<u8>],
+    verification_key: Option<&G>,
+    hashed_messages: &[G::Signature],
+) -> Result<(), String> {
+    let verification_key = verification_key.ok_or("no verification share for sender")?;
+    if shares.len() != hashed_messages.len() {
+        return Err(format!(
+            "sent {} shares for {} messages",
+            shares.len(),
+            hashed_messages.len()
         ));
     }
 
-    signing_state.signature = Some(as_sig.as_bytes().to_vec());
-    signing_state.secret_key = Some(secret_key);
+    let mut combined_share = G::Signature::identity();
+    let mut combined_hash = G::Signature::identity();
+    for (share, hashed_message) in shares.iter().zip(hashed_messages) {
+        let share =
+            sig_share_from_bytes::<G>(share).map_err(|e| format!("malformed share: {e}"))?;
+        let weight = if hashed_messages.len() == 1 {
+            Scalar::ONE
+        } else {
+            Scalar::random(&mut OsRng)
+        };
+        combined_share += share * weight;
+        combined_hash += *hashed_message * weight;
+    }
 
-    Ok(signing_state)
+    if !verification_key.core_verify(combined_hash, combined_share) {
+        return Err("shares do not verify against the sender's verification share".into());
+    }
+    Ok(())
+}
+
+/// Combines `count` signatures from the signature shares of each party,
+/// interpolating at zero over the parties' DKG identifiers.
+fn combine_sig_shares<G: KeyGroup>(
+    sig_shares: &BTreeMap<PartyIndex, Vec<G::Signature>>,
+    count: usize,
+) -> Result<Vec<G::Signature>, SigningError> {
+    let ids = sig_shares
+        .keys()
+        .map(|sender| participant_id(*sender))
+        .collect::<Vec<_>>();
+
+    let mut combined_signatures = vec![G::Signature::identity(); count];
+    for (sender, shares) in sig_shares {
+        let coefficient = lagrange_coefficient(participant_id(*sender), &ids).ok_or_else(|| {
+            SigningError::MpcError("Duplicate participant identifiers".to_string())
+        })?;
+        for (combined, share) in combined_signatures.iter_mut().zip(shares) {
+            *combined += *share * coefficient;
+        }
+    }
+    Ok(combined_signatures)
+}
+
+fn sig_share_from_bytes<G: KeyGroup>(bytes: &[u8]) -> Result<G::Signature, SigningError> {
+    G::Signature::from_compressed(bytes)
+        .ok_or_else(|| SigningError::MpcError("Failed to decode signature share".to_string()))
+}
+
+/// Messages store for a threshold round: outputs as soon as `t` valid
+/// messages, counting the local party's own, have been received, or once
+/// every other sender has sent one. Messages from other parties are ignored.
+struct ThresholdRoundInput<M> {
+    i: PartyIndex,
+    n: u16,
+    t: u16,
+    senders: BTreeSet<PartyIndex>,
+    validate: MessageValidator<M>,
+    messages_ids: BTreeMap<PartyIndex, MsgId>,
+    messages: BTreeMap<PartyIndex, M>,
+    rejected: BTreeMap<PartyIndex, String>,
+}
+
+/// Checks a message from the given sender, returning why it is invalid.
+type MessageValidator<M> = Box<dyn Fn(PartyIndex, &M) -> Result<(), String> + Send>;
+
+/// Valid messages of a threshold round, and why the others were rejected.
+struct ThresholdOutput<M> {
+    messages: BTreeMap<PartyIndex, M>,
+    rejected: BTreeMap<PartyIndex, String>,
+}
+
+impl<M> ThresholdRoundInput<M> {
+    fn new(
+        i: PartyIndex,
+        n: u16,
+        t: u16,
+        senders: BTreeSet<PartyIndex>,
+        validate: impl Fn(PartyIndex, &M) -> Result<(), String> + Send + 'static,
+    ) -> Self {
+        Self {
+            i,
+            n,
+            t,
+            senders: senders.into_iter().filter(|j| *j < n).collect(),
+            validate: Box::new(validate),
+            messages_ids: BTreeMap::new(),
+            messages: BTreeMap::new(),
+            rejected: BTreeMap::new(),
+        }
+    }
+}
+
+impl<M: 'static> MessagesStore for ThresholdRoundInput<M> {
+    type Msg = M;
+    type Output = ThresholdOutput<M>;
+    type Error = RoundInputError;
+
+    fn add_message(&mut self, msg: Incoming<Self::Msg>) -> Result<(), Self::Error> {
+        if msg.msg_type != MessageType::Broadcast {
+            return Err(RoundInputError::MismatchedMessageType {
+                msg_id: msg.id,
+                expected: MessageType::Broadcast,
+                actual: msg.msg_type,
+            });
+        }
+        if msg.sender == self.i {
+            // Ignore own messages
+            return Ok(());
+        }
+        if msg.sender >= self.n {
+            return Err(RoundInputError::SenderIndexOutOfRange {
+                msg_id: msg.id,
+                sender: msg.sender,
+                n: self.n,
+            });
+        }
+        if !self.senders.contains(&msg.sender) {
+            warn!(
+                "[BLS-SIGN] Ignoring signature shares from party {}, which holds no share",
+                msg.sender
+            );
+            return Ok(());
+        }
+        if let Some(prev_id) = self.messages_ids.get(&msg.sender) {
+            return Err(RoundInputError::AttemptToOverwriteReceivedMsg {
+                msgs_ids: [*prev_id, msg.id],
+                sender: msg.sender,
+            });
+        }
+        self.messages_ids.insert(msg.sender, msg.id);
+        match (self.validate)(msg.sender, &msg.msg) {
+            Ok(()) => {
+                self.messages.insert(msg.sender, msg.msg);
+            }
+            Err(reason) => {
+                self.rejected.insert(msg.sender, reason);
+            }
+        }
+        Ok(())
+    }
+
+    fn wants_more(&self) -> bool {
+        let other_senders = self.senders.iter().filter(|j| **j != self.i).count();
+        self.messages.len() + 1 < usize::from(self.t) && self.messages_ids.len() < other_senders
+    }
+
+    fn output(self) -> Result<Self::Output, Self> {
+        if self.wants_more() {
+            Err(self)
+        } else {
+            Ok(ThresholdOutput {
+                messages: self.messages,
+                rejected: self.rejected,
+            })
+        }
+    }
 }
 
 async fn send_message<M, Msg>(
@@ -142,3 +402,145 @@ where
         .await
         .map_err(|e| SigningError::MpcError(e.to_string()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Shamir shares of `secret` for parties `0..n`, at their DKG identifiers.
+    fn shares(secret: Scalar, t: u16, n: u16) -> Vec<Scalar> {
+        let coefficients = std::iter::once(secret)
+            .chain((1..t).map(|_| Scalar::random(&mut OsRng)))
+            .collect::<Vec<_>>();
+        (0..n)
+            .map(|i| {
+                let x = participant_id(i);
+                coefficients
+                    .iter()
+                    .rev()
+                    .fold(Scalar::ZERO, |acc, c| acc * x + c)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn any_t_shares_combine_to_the_same_signature() {
+        let (t, n) = (3, 5);
+        let secret = Scalar::random(&mut OsRng);
+        let shares = shares(secret, t, n);
+        let hashed_messages = [
+            G2Projective::random(&mut OsRng),
+            G2Projective::random(&mut OsRng),
+        ];
+        let expected = hashed_messages
+            .iter()
+            .map(|hashed_message| *hashed_message * secret)
+            .collect::<Vec<_>>();
+
+        for subset in [[0, 1, 2], [2, 3, 4], [0, 2, 4], [1, 3, 4]] {
+            let sig_shares = subset
+                .iter()
+                .map(|&j| {
+                    let share = hashed_messages
+                        .iter()
+                        .map(|hashed_message| *hashed_message * shares[usize::from(j)])
+                        .collect();
+                    (j, share)
+                })
+                .collect::<BTreeMap<PartyIndex, Vec<_>>>();
+            let signatures =
+                combine_sig_shares::<G1Projective>(&sig_shares, hashed_messages.len()).unwrap();
+            assert_eq!(signatures, expected, "subset {subset:?}");
+        }
+    }
+
+    #[test]
+    fn fewer_than_t_shares_do_not_combine_to_the_signature() {
+        let secret = Scalar::random(&mut OsRng);
+        let shares = shares(secret, 3, 5);
+        let hashed_message = G1Projective::random(&mut OsRng);
+        let sig_shares = [
+            (1, vec![hashed_message * shares[1]]),
+            (4, vec![hashed_message * shares[4]]),
+        ]
+        .into_iter()
+        .collect::<BTreeMap<PartyIndex, _>>();
+        let signatures = combine_sig_shares::<G2Projective>(&sig_shares, 1).unwrap();
+        assert_ne!(signatures[0], hashed_message * secret);
+    }
+
+    fn incoming(id: MsgId, sender: PartyIndex, msg: u8) -> Incoming<u8> {
+        Incoming {
+            id,
+            sender,
+            msg_type: MessageType::Broadcast,
+            msg,
+        }
+    }
+
+    #[test]
+    fn threshold_round_stops_at_t() {
+        // Party 0 of a 3-of-5 key waits for two other valid shares
+        let mut round = ThresholdRoundInput::new(0, 5, 3, (0..5).collect(), |_, _: &u8| Ok(()));
+        assert!(round.wants_more());
+        round.add_message(incoming(0, 0, 0)).unwrap();
+        assert!(round.wants_more(), "own message must not count twice");
+        round.add_message(incoming(1, 3, 0)).unwrap();
+        assert!(round.wants_more());
+        round.add_message(incoming(2, 1, 0)).unwrap();
+        assert!(!round.wants_more());
+
+        let output = round.output().ok().unwrap();
+        assert_eq!(output.messages.keys().copied().collect::<Vec<_>>(), [1, 3]);
+        assert!(output.rejected.is_empty());
+    }
+
+    #[test]
+    fn threshold_round_waits_past_invalid_messages() {
+        let mut round =
+            ThresholdRoundInput::new(0, 4, 3, (0..4).collect(), |_, msg: &u8| match msg {
+                0 => Ok(()),
+                _ => Err("invalid".to_string()),
+            });
+        round.add_message(incoming(0, 1, 1)).unwrap();
+        round.add_message(incoming(1, 2, 0)).unwrap();
+        assert!(
+            round.wants_more(),
+            "a rejected share does not count towards t"
+        );
+        assert!(round.add_message(incoming(2, 1, 0)).is_err());
+        round.add_message(incoming(3, 3, 0)).unwrap();
+        assert!(!round.wants_more());
+
+        let output = round.output().ok().unwrap();
+        assert_eq!(output.messages.keys().copied().collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(output.rejected.keys().copied().collect::<Vec<_>>(), [1]);
+    }
+
+    #[test]
+    fn threshold_round_stops_once_every_party_sent() {
+        let mut round = ThresholdRoundInput::new(2, 3, 3, (0..3).collect(), |_, _: &u8| {
+            Err("invalid".to_string())
+        });
+        round.add_message(incoming(0, 0, 0)).unwrap();
+        assert!(round.wants_more());
+        round.add_message(incoming(1, 1, 0)).unwrap();
+        assert!(!round.wants_more());
+        assert_eq!(round.output().ok().unwrap().rejected.len(), 2);
+    }
+
+    #[test]
+    fn threshold_round_ignores_parties_without_a_share() {
+        // Party 3 was left out of the DKG and holds no share
+        let mut round = ThresholdRoundInput::new(0, 4, 3, [0, 1, 2].into(), |_, _: &u8| Ok(()));
+        round.add_message(incoming(0, 3, 0)).unwrap();
+        round.add_message(incoming(1, 1, 0)).unwrap();
+        assert!(round.wants_more());
+        round.add_message(incoming(2, 2, 0)).unwrap();
+        assert!(!round.wants_more());
+
+        let output = round.output().ok().unwrap();
+        assert_eq!(output.messages.keys().copied().collect::<Vec<_>>(), [1, 2]);
+        assert!(output.rejected.is_empty());
+    }
+}
@@ -0,0 +1,475 @@
+use crate::keygen_state_machine::{BlsState, DkgCheckpoint};
+use crate::metrics::metrics;
+use crate::secret::SecretShare;
+use blueprint_sdk::stores::local_database::LocalDatabase;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::info;
+use zeroize::Zeroize;
+
+/// Environment variable pointing to a file holding the hex-encoded 32-byte
+/// store encryption key. When unset, the key is derived from the operator's
+/// ECDSA keystore identity.
+pub const STORE_KEY_FILE_ENV: &str = "BLS_STORE_KEY_FILE";
+
+const STORE_KEY_INFO: &[u8] = b"bls-blueprint/store-key/v1";
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    #[error("Failed to open store: {0}")]
+    Open(String),
+    #[error("Store error: {0}")]
+    Database(String),
+    #[error("Invalid store encryption key: {0}")]
+    InvalidKey(String),
+    #[error(
+        "Failed to decrypt store entry {0}: the store encryption key does not match the one it was written with"
+    )]
+    Decryption(String),
+    #[error("Failed to encrypt store entry: {0}")]
+    Encryption(String),
+    #[error("Key entry {0} not found")]
+    NotFound(String),
+}
+
+impl From<StoreError> for String {
+    fn from(err: StoreError) -> Self {
+        err.to_string()
+    }
+}
+
+/// Resolves the store encryption key, preferring an explicitly supplied key
+/// file over derivation from the operator's secret key.
+pub fn store_encryption_key(operator_secret: &[u8]) -> Result<[u8; 32], StoreError> {
+    match std::env::var(STORE_KEY_FILE_ENV) {
+        Ok(path) => {
+            let contents = std::fs::read_to_string(&path)
+                .map_err(|e| StoreError::InvalidKey(format!("{path}: {e}")))?;
+            let bytes = hex::decode(contents.trim().trim_start_matches("0x"))
+                .map_err(|e| StoreError::InvalidKey(format!("{path}: {e}")))?;
+            bytes
+                .try_into()
+                .map_err(|_| StoreError::InvalidKey(format!("{path}: expected 32 bytes")))
+        }
+        Err(_) => {
+            let mut key = [0u8; 32];
+            Hkdf::<Sha256>::new(None, operator_secret)
+                .expand(STORE_KEY_INFO, &mut key)
+                .map_err(|e| StoreError::InvalidKey(e.to_string()))?;
+            Ok(key)
+        }
+    }
+}
+
+/// Key share store that keeps secret shares encrypted at rest.
+///
+/// Entries are regular [`BlsState`] values in which `secret_key_bytes` is
+/// replaced by `encrypted_secret_key`. Callers always see the decrypted form.
+pub struct ShareStore {
+    db: LocalDatabase<BlsState>,
+    cipher: ChaCha20Poly1305,
+    /// Serializes read-modify-write updates of entries
+    updates: Mutex<()>,
+}
+
+impl ShareStore {
+    /// Opens the store, verifying that existing shares decrypt with `key`
+    /// and encrypting any plaintext shares left by earlier versions.
+    pub fn open(path: impl AsRef<Path>, key: &[u8; 32]) -> Result<Self, StoreError> {
+        let db = LocalDatabase::open(path).map_err(|e| StoreError::Open(e.to_string()))?;
+        let store = Self {
+            db,
+            cipher: ChaCha20Poly1305::new(Key::from_slice(key)),
+            updates: Mutex::new(()),
+        };
+
+        for (entry_key, state) in store.entries()? {
+            if state.encrypted_secret_key.is_some() {
+                store.decrypt(&entry_key, state)?;
+            } else if state.secret_key_bytes.is_some() {
+                info!("Encrypting plaintext key share {entry_key}");
+                store.set(&entry_key, state)?;
+            }
+        }
+
+        store.refresh_key_metrics();
+        Ok(store)
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<BlsState>, StoreError> {
+        self.db
+            .get(key)
+            .map_err(|e| StoreError::Database(e.to_string()))?
+            .map(|state| self.decrypt(key, state))
+            .transpose()
+    }
+
+    pub fn set(&self, key: &str, state: BlsState) -> Result<(), StoreError> {
+        let state = self.encrypt(key, state)?;
+        self.db
+            .set(key, state)
+            .map_err(|e| StoreError::Database(e.to_string()))?;
+        self.refresh_key_metrics();
+        Ok(())
+    }
+
+    /// Applies `f` to the entry under `key` and writes it back if `f`
+    /// succeeds. Updates are serialized so concurrent ones do not lose each
+    /// other's changes.
+    pub fn update<T, E>(
+        &self,
+        key: &str,
+        f: impl FnOnce(&mut BlsState) -> Result<T, E>,
+    ) -> Result<T, E>
+    where
+        E: From<StoreError>,
+    {
+        let _guard = self.updates.lock().unwrap_or_else(|e| e.into_inner());
+        let mut state = self
+            .get(key)?
+            .ok_or_else(|| StoreError::NotFound(key.to_string()))?;
+        let result = f(&mut state)?;
+        self.set(key, state)?;
+        Ok(result)
+    }
+
+    pub fn remove(&self, key: &str) -> Result<(), StoreError> {
+        self.db
+            .remove(key)
+            .map_err(|e| StoreError::Database(e.to_string()))?;
+        self.refresh_key_metrics();
+        Ok(())
+    }
+
+    /// Finds the entry of the key generated by the keygen job `call_id`,
+    /// whatever committee size it is stored under.
+    pub fn find_by_call_id(&self, call_id: u64) -> Result<Option<(String, BlsState)>, StoreError> {
+        self.entries()?
+            .into_iter()
+            .find(|(_, state)| state.call_id == call_id)
+            .map(|(key, state)| Ok((key.clone(), self.decrypt(&key, state)?)))
+            .transpose()
+    }
+
+    /// Updates the stored key gauges from the current entries.
+    fn refresh_key_metrics(&self) {
+        let Ok(entries) = self.entries() else {
+            return;
+        };
+        let retired = entries
+            .iter()
+            .filter(|(_, state)| state.retired_at_block.is_some())
+            .count();
+        let stored_keys = &metrics().stored_keys;
+        stored_keys
+            .with_label_values(&["active"])
+            .set((entries.len() - retired) as i64);
+        stored_keys
+            .with_label_values(&["retired"])
+            .set(retired as i64);
+    }
+
+    fn entries(&self) -> Result<Vec<(String, BlsState)>, StoreError> {
+        self.db
+            .entries()
+            .map_err(|e| StoreError::Database(e.to_string()))
+    }
+
+    fn encrypt(&self, key: &str, mut state: BlsState) -> Result<BlsState, StoreError> {
+        let Some(secret) = state.secret_key_bytes.take() else {
+            return Ok(state);
+        };
+        state.encrypted_secret_key = Some(seal(&self.cipher, key, secret.expose())?);
+        Ok(state)
+    }
+
+    fn decrypt(&self, key: &str, mut state: BlsState) -> Result<BlsState, StoreError> {
+        let Some(sealed) = state.encrypted_secret_key.take() else {
+            return Ok(state);
+        };
+        let secret = unseal(&self.cipher, key, &sealed)?;
+        state.secret_key_bytes = Some(SecretShare::new(secret));
+        Ok(state)
+    }
+}
+
+/// A keygen checkpoint as stored: the job's call ID and block in the
+/// clear, so restarts can pick up exactly the interrupted jobs, and the
+/// checkpoint sealed, as it holds the DKG's randomness.
+#[derive(Clone, Serialize, Deserialize)]
+struct StoredCheckpoint {
+    call_id: u64,
+    block_number: u64,
+    sealed: Vec<u8>,
+}
+
+/// Store of the checkpoints of keygen DKGs in progress, keyed by session ID.
+pub struct CheckpointStore {
+    db: LocalDatabase<StoredCheckpoint>,
+    cipher: ChaCha20Poly1305,
+}
+
+impl CheckpointStore {
+    pub fn open(path: impl AsRef<Path>, key: &[u8; 32]) -> Result<Self, StoreError> {
+        let db = LocalDatabase::open(path).map_err(|e| StoreError::Open(e.to_string()))?;
+        Ok(Self {
+            db,
+            cipher: ChaCha20Poly1305::new(Key::from_slice(key)),
+        })
+    }
+
+    pub fn get(&self, session: &str) -> Result<Option<DkgCheckpoint>, StoreError> {
+        let Some(stored) = self
+            .db
+            .get(session)
+            .map_err(|e| StoreError::Database(e.to_string()))?
+        else {
+            return Ok(None);
+        };
+        let mut bytes = unseal(&self.cipher, session, &stored.sealed)?;
+        let checkpoint = serde_json::from_slice(&bytes)
+            .map_err(|e| StoreError::Database(format!("Invalid checkpoint {session}: {e}")));
+        bytes.zeroize();
+        checkpoint.map(Some)
+    }
+
+    /// Saves the checkpoint of the keygen job `call_id` from `block_number`.
+    pub fn set(
+        &self,
+        session: &str,
+        call_id: u64,
+        block_number: u64,
+        checkpoint: &DkgCheckpoint,
+    ) -> Result<(), StoreError> {
+        let mut bytes = serde_json::to_vec(checkpoint)
+            .map_err(|e| StoreError::Database(format!("Invalid checkpoint {session}: {e}")))?;
+        let sealed = seal(&self.cipher, session, &bytes);
+        bytes.zeroize();
+        let stored = StoredCheckpoint {
+            call_id,
+            block_number,
+            sealed: sealed?,
+        };
+        self.db
+            .set(session, stored)
+            .map_err(|e| StoreError::Database(e.to_string()))
+    }
+
+    pub fn remove(&self, session: &str) -> Result<(), StoreError> {
+        self.db
+            .remove(session)
+            .map_err(|e| StoreError::Database(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Call IDs of the keygen jobs with a checkpoint, with their blocks.
+    pub fn interrupted_jobs(&self) -> Result<BTreeMap<u64, u64>, StoreError> {
+        Ok(self
+            .db
+            .values()
+            .map_err(|e| StoreError::Database(e.to_string()))?
+            .iter()
+            .map(|stored| (stored.call_id, stored.block_number))
+            .collect())
+    }
+}
+
+/// Encrypts `secret` as nonce || ciphertext, bound to the entry `key`.
+fn seal(cipher: &ChaCha20Poly1305, key: &str, secret: &[u8]) -> Result<Vec<u8>, StoreError> {
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: secret,
+                aad: key.as_bytes(),
+            },
+        )
+        .map_err(|e| StoreError::Encryption(e.to_string()))?;
+
+    let mut sealed = nonce.to_vec();
+    sealed.extend(ciphertext);
+    Ok(sealed)
+}
+
+/// Decrypts what [`seal`] encrypted for the entry `key`.
+fn unseal(cipher: &ChaCha20Poly1305, key: &str, sealed: &[u8]) -> Result<Vec<u8>, StoreError> {
+    if sealed.len() < NONCE_LEN {
+        return Err(StoreError::Decryption(key.to_string()));
+    }
+
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: key.as_bytes(),
+            },
+        )
+        .map_err(|_| StoreError::Decryption(key.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const KEY: [u8; 32] = [7; 32];
+
+    /// A fresh store path, removed again when dropped.
+    struct TempStore(PathBuf);
+
+    impl TempStore {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "bls-store-{name}-{}-{:?}.json",
+                std::process::id(),
+                std::thread::current().id()
+            ));
+            let _ = std::fs::remove_file(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for TempStore {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn state(secret: &[u8]) -> BlsState {
+        BlsState {
+            secret_key_bytes: Some(SecretShare::new(secret.to_vec())),
+            call_id: 3,
+            t: 2,
+            ..Default::default()
+        }
+    }
+
+    /// The entry under `key` as written to disk.
+    fn raw_entry(path: &Path, key: &str) -> BlsState {
+        LocalDatabase::<BlsState>::open(path)
+            .unwrap()
+            .get(key)
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn shares_round_trip_encrypted() {
+        let path = TempStore::new("round-trip");
+        let store = ShareStore::open(&path.0, &KEY).unwrap();
+        store.set("entry", state(&[1; 32])).unwrap();
+
+        let on_disk = raw_entry(&path.0, "entry");
+        assert!(on_disk.secret_key_bytes.is_none());
+        let sealed = on_disk.encrypted_secret_key.unwrap();
+        assert!(!sealed.windows(32).any(|window| window == [1; 32]));
+
+        let state = store.get("entry").unwrap().unwrap();
+        assert_eq!(state.secret_key_bytes.unwrap().expose(), [1; 32]);
+        let (entry_key, state) = store.find_by_call_id(3).unwrap().unwrap();
+        assert_eq!(entry_key, "entry");
+        assert_eq!(state.secret_key_bytes.unwrap().expose(), [1; 32]);
+
+        // Reopening with the same key reads the share back
+        drop(store);
+        let store = ShareStore::open(&path.0, &KEY).unwrap();
+        let state = store.get("entry").unwrap().unwrap();
+        assert_eq!(state.secret_key_bytes.unwrap().expose(), [1; 32]);
+    }
+
+    #[test]
+    fn plaintext_shares_are_encrypted_on_open() {
+        let path = TempStore::new("migrate");
+        LocalDatabase::<BlsState>::open(&path.0)
+            .unwrap()
+            .set("entry", state(&[2; 32]))
+            .unwrap();
+
+        let store = ShareStore::open(&path.0, &KEY).unwrap();
+
+        let on_disk = raw_entry(&path.0, "entry");
+        assert!(on_disk.secret_key_bytes.is_none());
+        assert!(on_disk.encrypted_secret_key.is_some());
+        let state = store.get("entry").unwrap().unwrap();
+        assert_eq!(state.secret_key_bytes.unwrap().expose(), [2; 32]);
+        assert_eq!(state.call_id, 3);
+        assert_eq!(state.t, 2);
+    }
+
+    #[test]
+    fn wrong_key_fails_to_decrypt() {
+        let path = TempStore::new("wrong-key");
+        ShareStore::open(&path.0, &KEY)
+            .unwrap()
+            .set("entry", state(&[3; 32]))
+            .unwrap();
+
+        match ShareStore::open(&path.0, &[8; 32]) {
+            Err(StoreError::Decryption(entry_key)) => assert_eq!(entry_key, "entry"),
+            Err(e) => panic!("unexpected error: {e}"),
+            Ok(_) => panic!("opened the store with the wrong key"),
+        }
+    }
+
+    #[test]
+    fn shares_are_bound_to_their_entry() {
+        let path = TempStore::new("moved");
+        let store = ShareStore::open(&path.0, &KEY).unwrap();
+        store.set("entry", state(&[4; 32])).unwrap();
+
+        // A sealed share copied to another entry does not decrypt there
+        store.db.set("other", raw_entry(&path.0, "entry")).unwrap();
+        assert!(matches!(
+            store.get("other"),
+            Err(StoreError::Decryption(entry_key)) if entry_key == "other"
+        ));
+    }
+
+    #[test]
+    fn checkpoints_round_trip_sealed() {
+        let path = TempStore::new("checkpoints");
+        let store = CheckpointStore::open(&path.0, &KEY).unwrap();
+        assert!(store.interrupted_jobs().unwrap().is_empty());
+
+        let checkpoint = DkgCheckpoint::new();
+        store.set("late", 2, 20, &checkpoint).unwrap();
+        store.set("early", 1, 10, &checkpoint).unwrap();
+        assert_eq!(
+            store.interrupted_jobs().unwrap(),
+            BTreeMap::from([(1, 10), (2, 20)])
+        );
+
+        let restored = store.get("early").unwrap().unwrap();
+        assert_eq!(
+            serde_json::to_vec(&restored).unwrap(),
+            serde_json::to_vec(&checkpoint).unwrap()
+        );
+
+        // The seed is not stored in the clear
+        let raw = std::fs::read(&path.0).unwrap();
+        let seed =
+            serde_json::to_string(&serde_json::to_value(&checkpoint).unwrap()["seed"]).unwrap();
+        assert!(!String::from_utf8_lossy(&raw).contains(&seed));
+
+        assert!(matches!(
+            CheckpointStore::open(&path.0, &[8; 32])
+                .unwrap()
+                .get("early"),
+            Err(StoreError::Decryption(_))
+        ));
+
+        store.remove("early").unwrap();
+        assert!(store.get("early").unwrap().is_none());
+        assert_eq!(store.interrupted_jobs().unwrap(), BTreeMap::from([(2, 20)]));
+    }
+}
@@ -0,0 +1,80 @@
+use round_based::PartyIndex;
+use tracing::Span;
+
+/// Span of one job's protocol execution on this operator.
+///
+/// Every operator derives the same execution ID for a job. With the `otel`
+/// feature the span joins a trace whose ID is taken from it, so the exported
+/// spans of all operators taking part in the job form a single trace.
+pub(crate) fn job_span(
+    job: &'static str,
+    service_id: u64,
+    call_id: u64,
+    execution_id: &[u8; 32],
+    party: PartyIndex,
+) -> Span {
+    let span = tracing::info_span!(
+        "bls_job",
+        job,
+        service_id,
+        call_id,
+        eid = %hex::encode(execution_id),
+        party,
+    );
+
+    #[cfg(feature = "otel")]
+    {
+        use opentelemetry::trace::{
+            SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState,
+        };
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        let (trace_id, rest) = execution_id.split_at(16);
+        let parent = SpanContext::new(
+            TraceId::from_bytes(trace_id.try_into().expect("16 bytes")),
+            SpanId::from_bytes(rest[..8].try_into().expect("8 bytes")),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        );
+        let _ = span.set_parent(opentelemetry::Context::new().with_remote_span_context(parent));
+    }
+
+    span
+}
+
+/// Tracing layer exporting spans over OTLP/HTTP.
+///
+/// The exporter is configured through the standard `OTEL_EXPORTER_OTLP_*`
+/// environment variables and is only installed when
+/// `OTEL_EXPORTER_OTLP_ENDPOINT` is set.
+#[cfg(feature = "otel")]
+pub fn otel_layer<S>() -> Result<Option<impl tracing_subscriber::Layer<S>>, String>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_sdk::Resource;
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+
+    if std::env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").is_none() {
+        return Ok(None);
+    }
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .build()
+        .map_err(|e| format!("Failed to build OTLP exporter: {e}"))?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name("bls-blueprint")
+                .build(),
+        )
+        .build();
+    let tracer = provider.tracer("bls-blueprint");
+    opentelemetry::global::set_tracer_provider(provider);
+
+    Ok(Some(tracing_opentelemetry::layer().with_tracer(tracer)))
+}